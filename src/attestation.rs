@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use crate::{logic::AdmitError, oci, signature};
+
+/// Media type of a layer containing a DSSE envelope.
+const DSSE_ENVELOPE: &str = "application/vnd.dsse.envelope.v1+json";
+
+/// Payload type of an in-toto statement.
+const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+/// Predicate type prefix of SLSA provenance, of any version.
+const SLSA_PROVENANCE: &str = "https://slsa.dev/provenance/";
+
+/// Predicate type prefixes of software bills of materials.
+const SBOM: [&str; 2] = ["https://spdx.dev/Document", "https://cyclonedx.org/bom"];
+
+/// Admits images by the in-toto attestations attached to them.
+#[derive(Clone)]
+pub(crate) struct Policy {
+    verifier: signature::Verifier,
+    require_provenance: bool,
+    require_sbom: bool,
+    builder_ids: Vec<String>,
+    source_repositories: Vec<String>,
+    branches: Vec<String>,
+}
+
+/// <https://github.com/in-toto/attestation/blob/main/spec/v1/statement.md>
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Statement {
+    subject: Vec<Subject>,
+    predicate_type: String,
+    #[serde(default)]
+    predicate: serde_json::Value,
+}
+
+#[derive(serde::Deserialize)]
+struct Subject {
+    digest: BTreeMap<String, String>,
+}
+
+/// Properties of a SLSA provenance predicate evaluated by the policy.
+struct Provenance {
+    builder_id: Option<String>,
+    source_repository: Option<String>,
+    branch: Option<String>,
+}
+
+impl Policy {
+    /// Creates a new `Policy` instance.
+    ///
+    /// Attestations must be signed by a key trusted by the `verifier`.
+    pub(crate) fn new(
+        verifier: signature::Verifier,
+        configuration: crate::configuration::Attestation,
+    ) -> Policy {
+        Policy {
+            verifier,
+            require_provenance: configuration.require_provenance,
+            require_sbom: configuration.require_sbom,
+            builder_ids: configuration.builder_ids,
+            source_repositories: configuration
+                .source_repositories
+                .iter()
+                .map(|source_repository| normalize_repository(source_repository))
+                .collect(),
+            branches: configuration.branches,
+        }
+    }
+
+    /// Evaluates the attestations of the manifest `digests`.
+    ///
+    /// Attestations are looked up through the `sha256-<digest>.att` tag convention and the
+    /// referrers API.
+    pub(crate) async fn evaluate(
+        &self,
+        client: &crate::http::Client,
+        upstream: &oci::Upstream,
+        name: &str,
+        digests: &[String],
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<Result<(), AdmitError>> {
+        let mut statements = Vec::new();
+
+        for digest in digests {
+            statements.extend(
+                self.statements(client, upstream, name, digest, authorization)
+                    .await?,
+            );
+        }
+
+        Ok(self.admitted(&statements))
+    }
+
+    /// Fetches the verified in-toto statements about the manifest `digest`.
+    async fn statements(
+        &self,
+        client: &crate::http::Client,
+        upstream: &oci::Upstream,
+        name: &str,
+        digest: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<Vec<Statement>> {
+        let mut manifests = Vec::new();
+
+        if let Some(manifest) = upstream
+            .manifest(
+                client,
+                name,
+                &signature::signature_tag(digest, "att"),
+                authorization,
+            )
+            .await?
+        {
+            manifests.push(manifest);
+        }
+
+        let referrers = upstream
+            .referrers(client, name, digest, None, authorization)
+            .await?;
+
+        for referrer in referrers.manifests {
+            if let Some(manifest) = upstream
+                .manifest(client, name, &referrer.digest, authorization)
+                .await?
+            {
+                manifests.push(manifest);
+            }
+        }
+
+        let mut statements = Vec::new();
+
+        for layer in manifests
+            .iter()
+            .flat_map(oci::manifest::Manifest::layers)
+            .filter(|layer| layer.media_type == DSSE_ENVELOPE)
+        {
+            let envelope = upstream
+                .blob(client, name, &layer.digest, authorization)
+                .await?;
+
+            let Some((payload_type, payload)) = self.verifier.verify_envelope(&envelope) else {
+                tracing::warn!(digest = %layer.digest, "Attestation signature could not be verified");
+                continue;
+            };
+
+            if payload_type != IN_TOTO_PAYLOAD_TYPE {
+                continue;
+            }
+
+            let statement = match serde_json::from_slice::<Statement>(&payload) {
+                Ok(statement) => statement,
+                Err(error) => {
+                    tracing::warn!(?error, "Failed to parse attestation");
+                    continue;
+                }
+            };
+
+            let is_subject = statement.subject.iter().any(|subject| {
+                subject
+                    .digest
+                    .iter()
+                    .any(|(algorithm, encoded)| digest == format!("{algorithm}:{encoded}"))
+            });
+
+            if is_subject {
+                statements.push(statement);
+            }
+        }
+
+        Ok(statements)
+    }
+
+    /// Checks if the verified statements satisfy the policy.
+    fn admitted(&self, statements: &[Statement]) -> Result<(), AdmitError> {
+        let provenances = statements
+            .iter()
+            .filter(|statement| statement.predicate_type.starts_with(SLSA_PROVENANCE))
+            .map(|statement| Provenance::from_predicate(&statement.predicate))
+            .collect::<Vec<_>>();
+
+        let constrained = !self.builder_ids.is_empty()
+            || !self.source_repositories.is_empty()
+            || !self.branches.is_empty();
+
+        if (self.require_provenance || constrained) && provenances.is_empty() {
+            return Err(AdmitError::ProvenanceNotFound);
+        }
+
+        if constrained
+            && !provenances
+                .iter()
+                .any(|provenance| self.is_trusted(provenance))
+        {
+            return Err(AdmitError::ProvenanceRejected);
+        }
+
+        let has_sbom = statements.iter().any(|statement| {
+            SBOM.iter()
+                .any(|sbom| statement.predicate_type.starts_with(sbom))
+        });
+
+        if self.require_sbom && !has_sbom {
+            return Err(AdmitError::SbomNotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the provenance satisfies every configured constraint.
+    fn is_trusted(&self, provenance: &Provenance) -> bool {
+        let builder_id = self.builder_ids.is_empty()
+            || provenance
+                .builder_id
+                .as_ref()
+                .is_some_and(|builder_id| self.builder_ids.contains(builder_id));
+
+        let source_repository = self.source_repositories.is_empty()
+            || provenance
+                .source_repository
+                .as_ref()
+                .is_some_and(|source_repository| {
+                    self.source_repositories
+                        .contains(&normalize_repository(source_repository))
+                });
+
+        let branch = self.branches.is_empty()
+            || provenance.branch.as_ref().is_some_and(|branch| {
+                self.branches.iter().any(|expected| {
+                    branch == expected || *branch == format!("refs/heads/{expected}")
+                })
+            });
+
+        builder_id && source_repository && branch
+    }
+}
+
+impl Provenance {
+    /// Extracts the evaluated properties of SLSA provenance v0.2 and v1 predicates.
+    fn from_predicate(predicate: &serde_json::Value) -> Provenance {
+        let str = |pointer: &str| {
+            predicate
+                .pointer(pointer)
+                .and_then(serde_json::Value::as_str)
+                .map(ToString::to_string)
+        };
+
+        let builder_id = str("/builder/id").or_else(|| str("/runDetails/builder/id"));
+
+        // v0.2 records the source as `git+https://github.com/org/repo@refs/heads/main`.
+        let (source_repository, branch) = match str("/invocation/configSource/uri")
+            .or_else(|| str("/buildDefinition/resolvedDependencies/0/uri"))
+        {
+            Some(uri) => match uri.split_once('@') {
+                Some((repository, branch)) => {
+                    (Some(repository.to_string()), Some(branch.to_string()))
+                }
+                None => (Some(uri), None),
+            },
+            None => (None, None),
+        };
+
+        // v1 GitHub Actions builds record the workflow source separately.
+        Provenance {
+            builder_id,
+            source_repository: str("/buildDefinition/externalParameters/workflow/repository")
+                .or(source_repository),
+            branch: str("/buildDefinition/externalParameters/workflow/ref").or(branch),
+        }
+    }
+}
+
+/// Normalizes a source repository URI for comparison.
+fn normalize_repository(repository: &str) -> String {
+    let repository = repository.strip_prefix("git+").unwrap_or(repository);
+    let repository = repository.strip_suffix(".git").unwrap_or(repository);
+
+    repository.trim_end_matches('/').to_string()
+}
@@ -0,0 +1,20 @@
+/// Records an audit entry of an action taken through the gateway.
+///
+/// Audit entries are emitted as events of the `audit` target, so they can be routed separately
+/// from diagnostic events.
+pub(crate) fn record(
+    action: &str,
+    repository: &str,
+    reference: &str,
+    principal: Option<&str>,
+    outcome: &str,
+) {
+    tracing::info!(
+        target: "audit",
+        action,
+        repository,
+        reference,
+        principal = principal.unwrap_or("anonymous"),
+        outcome,
+    );
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use container_registry_gateway::{check, configuration, fixture};
+
+const USAGE: &str = "Usage:
+  container-registry-gateway-check [--format text|json] <image>
+  container-registry-gateway-check [--format text|json] test <policy> <fixtures>
+
+Evaluates the admission policy of the gateway for the image, exiting with 1 if it is denied.
+
+With test, evaluates the policy file against the fixture scan results of the directory, exiting
+with 1 if any verdict differs from the expected verdict.";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Text,
+    Json,
+}
+
+enum Command {
+    Check(String),
+    Test(PathBuf, PathBuf),
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(error) = set_up_logging() {
+        eprintln!("{error}");
+        std::process::exit(2);
+    }
+
+    let (format, command) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let outcome = match command {
+        Command::Check(image) => run(format, &image).await,
+        Command::Test(policy, fixtures) => test(format, &policy, &fixtures),
+    };
+
+    match outcome {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(2);
+        }
+    }
+}
+
+async fn run(format: Format, image: &str) -> container_registry_gateway::Result<bool> {
+    let configuration = configuration::load(&[])?;
+
+    let verdict = check::run(configuration, image).await?;
+
+    match format {
+        Format::Text => print!("{verdict}"),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&verdict)?),
+    }
+
+    Ok(verdict.admitted)
+}
+
+fn test(
+    format: Format,
+    policy: &std::path::Path,
+    fixtures: &std::path::Path,
+) -> container_registry_gateway::Result<bool> {
+    let report = fixture::evaluate(policy, fixtures)?;
+
+    match format {
+        Format::Text => print!("{report}"),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    Ok(report.mismatches.is_empty())
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> container_registry_gateway::Result<(Format, Command)> {
+    let mut format = Format::Text;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    _ => return Err("--format must be text or json".into()),
+                }
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') => positional.push(arg),
+            _ => return Err(format!("Unexpected argument {arg}").into()),
+        }
+    }
+
+    let command = match positional.as_slice() {
+        [image] => Command::Check(image.clone()),
+        [test, policy, fixtures] if test == "test" => Command::Test(policy.into(), fixtures.into()),
+        [] => return Err("Missing image".into()),
+        _ => return Err("Unexpected arguments".into()),
+    };
+
+    Ok((format, command))
+}
+
+/// Logs to stderr, keeping stdout for the verdict.
+fn set_up_logging() -> container_registry_gateway::Result<()> {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .try_init()
+}
@@ -1,8 +1,42 @@
-use container_registry_gateway::{configuration, server, shutdown};
+use std::path::PathBuf;
+
+use container_registry_gateway::{configuration, reload, server, shutdown};
 use tokio::net::TcpListener;
 
+const USAGE: &str = "Usage:
+  container-registry-gateway-server
+  container-registry-gateway-server config check [<file>]
+
+Without arguments, serves the gateway.
+
+With config check, validates the configuration, from the file if any, and prints the effective
+configuration with secrets redacted, exiting with 1 if it is invalid.";
+
 #[tokio::main]
 async fn main() -> container_registry_gateway::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => serve().await,
+        ["config", "check"] => config_check(None),
+        ["config", "check", file] => config_check(Some(PathBuf::from(file))),
+        ["-h" | "--help"] => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => {
+            eprintln!("Unexpected arguments\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    }
+}
+
+async fn serve() -> container_registry_gateway::Result<()> {
     set_up_logging()?;
 
     let configuration = configuration::load(&[])?;
@@ -13,11 +47,41 @@ async fn main() -> container_registry_gateway::Result<()> {
     ))
     .await?;
 
-    server::run(tcp_listener.into_std()?, shutdown::recv(), configuration).await?;
+    let (reloads_sender, reloads) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(reload::watch(reloads_sender));
+
+    server::run_with_reload(
+        tcp_listener.into_std()?,
+        shutdown::recv(),
+        configuration,
+        reloads,
+    )
+    .await?;
 
     Ok(())
 }
 
+fn config_check(file: Option<PathBuf>) -> container_registry_gateway::Result<()> {
+    let configuration = match file {
+        Some(file) => configuration::load_file(&file),
+        None => configuration::load(&[]),
+    };
+
+    match configuration {
+        Ok(configuration) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&configuration.redacted()?)?
+            );
+            Ok(())
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
+
 fn set_up_logging() -> container_registry_gateway::Result<()> {
     tracing_subscriber::fmt::try_init()
 }
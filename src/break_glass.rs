@@ -0,0 +1,222 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine as _;
+use hmac::Mac as _;
+
+use crate::secret;
+
+/// Header presenting a break-glass token on pulls.
+pub(crate) const HEADER: &str = "x-break-glass";
+
+/// Separator of a break-glass token appended to the username of basic credentials, as
+/// `username+break-glass=token`, for clients which cannot set headers on pulls.
+const USERNAME_SEPARATOR: &str = "+break-glass=";
+
+/// Issues and verifies break-glass tokens, letting pulls of a manifest bypass admission until the
+/// token expires.
+#[derive(Clone)]
+pub(crate) struct BreakGlass {
+    secret: secret::Source,
+    admin_token: secret::Source,
+    ttl: Duration,
+    max_ttl: Duration,
+}
+
+/// The claims of a break-glass token.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Claims {
+    pub(crate) repository: String,
+    pub(crate) digest: String,
+    /// Expiry, as seconds since the Unix epoch.
+    pub(crate) expires: u64,
+    pub(crate) reason: String,
+}
+
+impl BreakGlass {
+    /// Creates a new `BreakGlass` instance.
+    ///
+    /// # Errors
+    ///
+    /// If a secret file cannot be read, an error is returned.
+    pub(crate) fn new(
+        configuration: &crate::configuration::BreakGlass,
+    ) -> crate::Result<BreakGlass> {
+        Ok(BreakGlass {
+            secret: secret::Source::new(
+                configuration.secret.as_ref(),
+                configuration.secret_file.as_deref(),
+            )?,
+            admin_token: secret::Source::new(
+                configuration.admin_token.as_ref(),
+                configuration.admin_token_file.as_deref(),
+            )?,
+            ttl: Duration::from_secs(configuration.ttl),
+            max_ttl: Duration::from_secs(configuration.max_ttl),
+        })
+    }
+
+    /// Checks the request is authorized by the admin token, sent as a bearer token.
+    pub(crate) fn is_admin(&self, headers: &hyper::HeaderMap) -> bool {
+        headers
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| {
+                secret::constant_time_eq(
+                    token.as_bytes(),
+                    self.admin_token.get().expose().as_bytes(),
+                )
+            })
+    }
+
+    /// Issues a token for the manifest `digest` of the `repository`.
+    ///
+    /// The token is valid for `ttl`, or the default time to live, capped by the maximum time to
+    /// live.
+    ///
+    /// # Errors
+    ///
+    /// If the claims cannot be serialized, an error is returned.
+    pub(crate) fn issue(
+        &self,
+        repository: String,
+        digest: String,
+        ttl: Option<Duration>,
+        reason: String,
+    ) -> crate::Result<(String, Claims)> {
+        let expires = SystemTime::now() + ttl.unwrap_or(self.ttl).min(self.max_ttl);
+
+        let claims = Claims {
+            repository,
+            digest,
+            expires: expires.duration_since(UNIX_EPOCH)?.as_secs(),
+            reason,
+        };
+
+        let payload =
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?);
+        let signature =
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(self.sign(&payload));
+
+        Ok((format!("{payload}.{signature}"), claims))
+    }
+
+    /// Verifies the token grants pulls of any of the manifest `digests` of the `repository`.
+    ///
+    /// Returns the claims of the token, or `None` if it is forged, expired or bound to another
+    /// manifest.
+    pub(crate) fn verify(
+        &self,
+        token: &str,
+        repository: &str,
+        digests: &[String],
+    ) -> Option<Claims> {
+        let (payload, signature) = token.split_once('.')?;
+        let signature = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(signature)
+            .ok()?;
+
+        if !secret::constant_time_eq(&signature, &self.sign(payload)) {
+            return None;
+        }
+
+        let claims: Claims = serde_json::from_slice(
+            &base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(payload)
+                .ok()?,
+        )
+        .ok()?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+        (claims.expires > now
+            && claims.repository == repository
+            && digests.contains(&claims.digest))
+        .then_some(claims)
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut mac =
+            hmac::Hmac::<sha2::Sha256>::new_from_slice(self.secret.get().expose().as_bytes())
+                .expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+/// Request of a break-glass token.
+#[derive(serde::Deserialize)]
+pub(crate) struct IssueRequest {
+    pub(crate) repository: String,
+    pub(crate) digest: String,
+    /// Time to live of the token, in seconds.
+    pub(crate) ttl: Option<u64>,
+    pub(crate) reason: String,
+}
+
+/// A break-glass token, with its claims.
+#[derive(serde::Serialize)]
+pub(crate) struct Issued {
+    pub(crate) token: String,
+    #[serde(flatten)]
+    pub(crate) claims: Claims,
+}
+
+/// Moves a break-glass token appended to the username of basic credentials to the break-glass
+/// header, unless a token is already presented with the header.
+///
+/// The credentials are kept without the token, or removed if no username remains, so the upstream
+/// authorizes the pull as without a token.
+pub(crate) fn from_credentials(headers: &mut hyper::HeaderMap) {
+    let Some((username, password, token)) = headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(split_credentials)
+    else {
+        return;
+    };
+
+    let Ok(token) = hyper::header::HeaderValue::from_str(&token) else {
+        return;
+    };
+
+    headers.entry(HEADER).or_insert(token);
+
+    if username.is_empty() {
+        headers.remove(hyper::header::AUTHORIZATION);
+        return;
+    }
+
+    let credentials =
+        base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+
+    if let Ok(mut authorization) =
+        hyper::header::HeaderValue::from_str(&format!("Basic {credentials}"))
+    {
+        authorization.set_sensitive(true);
+        headers.insert(hyper::header::AUTHORIZATION, authorization);
+    }
+}
+
+/// Splits basic credentials into the username, the password and the break-glass token appended
+/// to the username, if any.
+fn split_credentials(authorization: &str) -> Option<(String, String, String)> {
+    let (scheme, credentials) = authorization.split_once(' ')?;
+
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    let credentials = base64::engine::general_purpose::STANDARD
+        .decode(credentials.trim())
+        .ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    let (username, token) = username.split_once(USERNAME_SEPARATOR)?;
+
+    Some((
+        username.to_string(),
+        password.to_string(),
+        token.to_string(),
+    ))
+}
@@ -0,0 +1,570 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use sha2::Digest as _;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+const DOCKER_CONTENT_DIGEST: &str = "docker-content-digest";
+
+/// Content addressed cache of blobs and manifests stored on the local disk.
+///
+/// Content is only stored after it has been verified against its digest, and the least recently
+/// used content is evicted once the cache exceeds its maximum size.
+#[derive(Clone)]
+pub(crate) struct Cache {
+    directory: PathBuf,
+    max_size: u64,
+    manifest_ttl: Duration,
+    index: Arc<Mutex<Index>>,
+}
+
+#[derive(Default)]
+struct Index {
+    clock: u64,
+    size: u64,
+    entries: HashMap<String, Entry>,
+    recency: BTreeMap<u64, String>,
+    tags: HashMap<String, Tag>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    size: u64,
+    media_type: String,
+    last_access: u64,
+}
+
+struct Tag {
+    digest: String,
+    expires_at: Instant,
+}
+
+impl Cache {
+    /// Creates a new `Cache` instance.
+    ///
+    /// Content already present in `directory` is indexed, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// If the directory cannot be created or read, an error is returned.
+    pub(crate) fn new(
+        directory: impl Into<PathBuf>,
+        max_size: u64,
+        manifest_ttl: Duration,
+    ) -> crate::Result<Cache> {
+        let directory = directory.into();
+
+        std::fs::create_dir_all(directory.join("sha256"))?;
+        std::fs::create_dir_all(directory.join("tmp"))?;
+
+        // partially downloaded content from a previous run is never resumed.
+        for dir_entry in std::fs::read_dir(directory.join("tmp"))? {
+            std::fs::remove_file(dir_entry?.path())?;
+        }
+
+        let mut existing = Vec::new();
+
+        for dir_entry in std::fs::read_dir(directory.join("sha256"))? {
+            let dir_entry = dir_entry?;
+            let file_name = dir_entry.file_name().to_string_lossy().to_string();
+
+            if file_name.ends_with(".media-type") {
+                continue;
+            }
+
+            let metadata = dir_entry.metadata()?;
+            let media_type = std::fs::read_to_string(dir_entry.path().with_extension("media-type"))
+                .unwrap_or_else(|_| "application/octet-stream".to_string());
+
+            existing.push((
+                metadata.modified()?,
+                format!("sha256:{file_name}"),
+                metadata.len(),
+                media_type,
+            ));
+        }
+
+        existing.sort();
+
+        let cache = Cache {
+            directory,
+            max_size,
+            manifest_ttl,
+            index: Arc::default(),
+        };
+
+        for (_, digest, size, media_type) in existing {
+            cache.insert(&digest, size, media_type);
+        }
+
+        Ok(cache)
+    }
+
+    /// Checks if the cache is stored in `directory`.
+    pub(crate) fn is_in(&self, directory: &str) -> bool {
+        self.directory == Path::new(directory)
+    }
+
+    /// Returns a cache sharing the content and index of this one, with new limits.
+    ///
+    /// Content exceeding a lower maximum size is evicted on the next store.
+    pub(crate) fn reconfigured(&self, max_size: u64, manifest_ttl: Duration) -> Cache {
+        Cache {
+            directory: self.directory.clone(),
+            max_size,
+            manifest_ttl,
+            index: self.index.clone(),
+        }
+    }
+
+    /// Serves a blob from the cache, falling back to the upstream.
+    ///
+    /// Cached blobs are only served once the upstream grants the client access to them. Blobs
+    /// fetched from the upstream are streamed to the client while they are stored, if the request
+    /// was a `GET`.
+    pub(crate) async fn blob(
+        &self,
+        client: &crate::http::Client,
+        proxy: &crate::oci::Proxy,
+        request: hyper::Request<hyper::Body>,
+        digest: &str,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        if self.contains(digest) {
+            if let Some(response) = authorize(client, proxy, &request).await? {
+                return Ok(response);
+            }
+
+            if let Some(response) = self.serve(request.method(), digest, None).await? {
+                return Ok(response);
+            }
+        }
+
+        if request.method() != hyper::Method::GET || self.path(digest).is_none() {
+            return proxy.send(client, request).await;
+        }
+
+        let mut response = proxy.send(client, request).await?;
+
+        // blobs are commonly served from a content delivery network.
+        if response.status().is_redirection() {
+            if let Some(location) = response.headers().get(hyper::header::LOCATION) {
+                let location = location.to_str()?.parse::<hyper::Uri>()?;
+
+                if location.scheme().is_some() {
+                    response = client.get(location).await?;
+                }
+            }
+        }
+
+        if response.status() != hyper::StatusCode::OK {
+            return Ok(response);
+        }
+
+        let (parts, body) = response.into_parts();
+        let (mut sender, streamed) = hyper::Body::channel();
+
+        let cache = self.clone();
+        let stored_digest = digest.to_string();
+
+        tokio::spawn(async move {
+            if let Err(error) = cache
+                .store(
+                    &stored_digest,
+                    "application/octet-stream",
+                    body,
+                    Some(&mut sender),
+                )
+                .await
+            {
+                tracing::error!(?error, digest = %stored_digest, "Failed to store blob");
+                // the client must not mistake the content for a complete blob.
+                sender.abort();
+            }
+        });
+
+        let mut response = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/octet-stream")
+            .header(DOCKER_CONTENT_DIGEST, digest);
+
+        if let Some(content_length) = parts.headers.get(hyper::header::CONTENT_LENGTH) {
+            response = response.header(hyper::header::CONTENT_LENGTH, content_length);
+        }
+
+        response.body(streamed).map_err(Into::into)
+    }
+
+    /// Serves a manifest from the cache, falling back to the upstream.
+    ///
+    /// Cached manifests are only served once the upstream grants the client access to them.
+    /// Manifests fetched from the upstream are stored if the request was a `GET`, tags are
+    /// resolved from the cache until the manifest time to live expires.
+    ///
+    /// `name` must identify the repository across upstreams.
+    pub(crate) async fn manifest(
+        &self,
+        client: &crate::http::Client,
+        proxy: &crate::oci::Proxy,
+        request: hyper::Request<hyper::Body>,
+        name: &str,
+        reference: &str,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        let accept = crate::oci::manifest::accepted(
+            request
+                .headers()
+                .get_all(hyper::header::ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok()),
+        );
+
+        let digest = if reference.contains(':') {
+            Some(reference.to_string())
+        } else {
+            self.tag(&format!("{name}:{reference}"))
+        };
+
+        if let Some(digest) = digest.as_ref().filter(|digest| self.contains(digest)) {
+            if let Some(response) = authorize(client, proxy, &request).await? {
+                return Ok(response);
+            }
+
+            if let Some(response) = self.serve(request.method(), digest, Some(&accept)).await? {
+                return Ok(response);
+            }
+        }
+
+        if request.method() != hyper::Method::GET {
+            return proxy.send(client, request).await;
+        }
+
+        let response = proxy.send(client, request).await?;
+
+        if response.status() != hyper::StatusCode::OK {
+            return Ok(response);
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+
+        let digest = format!("sha256:{:x}", sha2::Sha256::digest(&body));
+
+        let media_type = parts
+            .headers
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("application/octet-stream");
+
+        if reference.contains(':') && reference != digest {
+            tracing::warn!(%reference, %digest, "Manifest digest mismatch");
+        } else {
+            self.store(&digest, media_type, hyper::Body::from(body.clone()), None)
+                .await?;
+
+            if !reference.contains(':') {
+                self.index.lock().unwrap().tags.insert(
+                    format!("{name}:{reference}"),
+                    Tag {
+                        digest,
+                        expires_at: Instant::now() + self.manifest_ttl,
+                    },
+                );
+            }
+        }
+
+        Ok(hyper::Response::from_parts(parts, hyper::Body::from(body)))
+    }
+
+    /// Serves content from the cache.
+    ///
+    /// Returns `None` if the content is not cached, or is not of an acceptable media type.
+    async fn serve(
+        &self,
+        method: &hyper::Method,
+        digest: &str,
+        accept: Option<&[String]>,
+    ) -> crate::Result<Option<hyper::Response<hyper::Body>>> {
+        let Some(path) = self.path(digest) else {
+            return Ok(None);
+        };
+
+        let Some(entry) = self.touch(digest) else {
+            return Ok(None);
+        };
+
+        if let Some(accept) = accept {
+            if !crate::oci::manifest::is_acceptable(accept, &entry.media_type) {
+                return Ok(None);
+            }
+        }
+
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        tracing::debug!(%digest, "Serving from cache");
+
+        let response = hyper::Response::builder()
+            .status(hyper::StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, entry.media_type)
+            .header(hyper::header::CONTENT_LENGTH, entry.size)
+            .header(DOCKER_CONTENT_DIGEST, digest);
+
+        let body = if method == hyper::Method::HEAD {
+            hyper::Body::empty()
+        } else {
+            stream(file)
+        };
+
+        response.body(body).map(Some).map_err(Into::into)
+    }
+
+    /// Stores content in the cache, verifying it against its digest.
+    ///
+    /// The content is also sent to the `sender`, if any, as it is received. Content is still
+    /// stored if the receiver goes away.
+    ///
+    /// # Errors
+    ///
+    /// If the content does not match its digest, an error is returned.
+    async fn store(
+        &self,
+        digest: &str,
+        media_type: &str,
+        mut body: hyper::Body,
+        mut sender: Option<&mut hyper::body::Sender>,
+    ) -> crate::Result<()> {
+        use hyper::body::HttpBody as _;
+
+        let path = self.path(digest).ok_or("Unsupported digest algorithm")?;
+        let temporary_path = self.directory.join("tmp").join(format!(
+            "{}-{}",
+            std::process::id(),
+            self.index.lock().unwrap().next()
+        ));
+
+        let mut file = tokio::fs::File::create(&temporary_path).await?;
+        let mut hasher = sha2::Sha256::new();
+        let mut size = 0;
+
+        // the last chunk is held back until the content is verified, so a receiver never mistakes
+        // content not matching its digest for a complete blob.
+        let mut pending = None;
+
+        let written: crate::Result<()> = async {
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk?;
+                hasher.update(&chunk);
+                size += chunk.len() as u64;
+                file.write_all(&chunk).await?;
+
+                if let Some(previous) = pending.replace(chunk) {
+                    send(&mut sender, previous).await;
+                }
+            }
+
+            file.sync_all().await?;
+
+            Ok(())
+        }
+        .await;
+
+        drop(file);
+
+        let actual = format!("sha256:{:x}", hasher.finalize());
+
+        if let Err(error) = written {
+            tokio::fs::remove_file(&temporary_path).await?;
+            return Err(error);
+        }
+
+        if actual != digest {
+            tokio::fs::remove_file(&temporary_path).await?;
+            return Err(format!("Digest mismatch, expected {digest}, actual {actual}").into());
+        }
+
+        tokio::fs::write(path.with_extension("media-type"), media_type).await?;
+        tokio::fs::rename(&temporary_path, &path).await?;
+
+        self.insert(digest, size, media_type.to_string());
+
+        if let Some(chunk) = pending {
+            send(&mut sender, chunk).await;
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the content is cached.
+    fn contains(&self, digest: &str) -> bool {
+        self.index.lock().unwrap().entries.contains_key(digest)
+    }
+
+    /// Resolves a tag to a digest, if the tag has not expired.
+    fn tag(&self, name_reference: &str) -> Option<String> {
+        let mut index = self.index.lock().unwrap();
+
+        match index.tags.get(name_reference) {
+            Some(tag) if tag.expires_at > Instant::now() => Some(tag.digest.clone()),
+            Some(_) => {
+                index.tags.remove(name_reference);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Marks content as recently used.
+    fn touch(&self, digest: &str) -> Option<Entry> {
+        let mut index = self.index.lock().unwrap();
+        let clock = index.next();
+
+        let entry = index.entries.get_mut(digest)?;
+        let last_access = std::mem::replace(&mut entry.last_access, clock);
+        let entry = entry.clone();
+
+        index.recency.remove(&last_access);
+        index.recency.insert(clock, digest.to_string());
+
+        Some(entry)
+    }
+
+    /// Adds content to the index, evicting the least recently used content.
+    fn insert(&self, digest: &str, size: u64, media_type: String) {
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            let clock = index.next();
+
+            if let Some(previous) = index.entries.insert(
+                digest.to_string(),
+                Entry {
+                    size,
+                    media_type,
+                    last_access: clock,
+                },
+            ) {
+                index.size -= previous.size;
+                index.recency.remove(&previous.last_access);
+            }
+
+            index.size += size;
+            index.recency.insert(clock, digest.to_string());
+
+            let mut evicted = Vec::new();
+
+            while index.size > self.max_size {
+                let Some((_, digest)) = index.recency.pop_first() else {
+                    break;
+                };
+
+                if let Some(entry) = index.entries.remove(&digest) {
+                    index.size -= entry.size;
+                }
+
+                evicted.push(digest);
+            }
+
+            evicted
+        };
+
+        for digest in evicted {
+            tracing::debug!(%digest, "Evicting from cache");
+
+            if let Some(path) = self.path(&digest) {
+                for path in [path.with_extension("media-type"), path] {
+                    if let Err(error) = std::fs::remove_file(path) {
+                        tracing::warn!(?error, "Failed to evict from cache");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the path of the content on the local disk.
+    ///
+    /// Returns `None` if the digest is not a valid sha256 digest.
+    fn path(&self, digest: &str) -> Option<PathBuf> {
+        let encoded = digest.strip_prefix("sha256:")?;
+
+        if encoded.len() != 64 || !encoded.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        Some(
+            self.directory
+                .join("sha256")
+                .join(encoded.to_ascii_lowercase()),
+        )
+    }
+}
+
+impl Index {
+    fn next(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// Checks the upstream grants the client access to the content of the `request`, with a `HEAD`
+/// request on behalf of its credentials.
+///
+/// Returns the response of the upstream if access is not granted.
+async fn authorize(
+    client: &crate::http::Client,
+    proxy: &crate::oci::Proxy,
+    request: &hyper::Request<hyper::Body>,
+) -> crate::Result<Option<hyper::Response<hyper::Body>>> {
+    let mut head = hyper::Request::head(request.uri().clone()).body(hyper::Body::empty())?;
+    head.headers_mut().clone_from(request.headers());
+
+    let response = proxy.send(client, head).await?;
+
+    if response.status().is_success() || response.status().is_redirection() {
+        Ok(None)
+    } else {
+        Ok(Some(response))
+    }
+}
+
+/// Sends a chunk to the receiver, if it has not gone away.
+async fn send(sender: &mut Option<&mut hyper::body::Sender>, chunk: hyper::body::Bytes) {
+    if let Some(receiver) = sender.as_mut() {
+        if receiver.send_data(chunk).await.is_err() {
+            *sender = None;
+        }
+    }
+}
+
+/// Streams a file as a response body.
+fn stream(mut file: tokio::fs::File) -> hyper::Body {
+    let (mut sender, body) = hyper::Body::channel();
+
+    tokio::spawn(async move {
+        let mut buffer = vec![0; 64 * 1024];
+
+        loop {
+            match file.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(length) => {
+                    let chunk = hyper::body::Bytes::copy_from_slice(&buffer[..length]);
+
+                    if sender.send_data(chunk).await.is_err() {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    tracing::error!(?error);
+                    sender.abort();
+                    break;
+                }
+            }
+        }
+    });
+
+    body
+}
@@ -0,0 +1,119 @@
+use axum::Extension;
+
+use crate::{configuration, kubernetes, route, state::State};
+
+/// Verdict of the admission policy for an image.
+#[derive(Debug, serde::Serialize)]
+pub struct Verdict {
+    pub image: String,
+    pub digest: Option<String>,
+    pub admitted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vulnerabilities: Option<Vulnerabilities>,
+}
+
+/// Number of vulnerabilities of each severity reported by the scanner.
+#[derive(Debug, serde::Serialize)]
+pub struct Vulnerabilities {
+    pub critical: u32,
+    pub high: u32,
+    pub medium: u32,
+    pub low: u32,
+}
+
+/// Evaluates the admission policy of the gateway for the `image`, without pulling it.
+///
+/// The image is referenced as `[registry/]repository[:tag][@digest]`. Images of the gateway keep
+/// their repository, images of other registries are resolved through the upstream serving their
+/// registry.
+///
+/// # Errors
+///
+/// If the configuration is malformed, no upstream serves the registry of the image, or the
+/// upstream or the scanner cannot be queried, an error is returned.
+pub async fn run(
+    configuration: configuration::Configuration,
+    image: &str,
+) -> crate::Result<Verdict> {
+    let gateway = configuration
+        .kubernetes
+        .as_ref()
+        .map(|kubernetes| {
+            kubernetes
+                .gateway
+                .iter()
+                .chain(&kubernetes.registries)
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let state = Extension(State::new(configuration)?);
+    let mut parsed = kubernetes::Image::parse(image);
+
+    let (upstream, upstream_name) = match &parsed.registry {
+        Some(registry) if gateway.contains(registry) => {
+            state.oci_proxy.resolve(&parsed.repository, None)
+        }
+        registry => {
+            let registry = registry.as_deref().unwrap_or("docker.io");
+
+            state
+                .oci_proxy
+                .resolve_registry(registry, &parsed.repository)
+                .ok_or_else(|| format!("No upstream serves the registry {registry}"))?
+        }
+    };
+    let upstream_name = upstream_name.into_owned();
+
+    parsed.digest = route::admission_image_digest(&state, upstream, &upstream_name, &parsed)
+        .await
+        .map_err(|status| format!("Failed to resolve image digest, {status}"))?;
+
+    let route::Admission { admitted, scan } =
+        route::admission_image_admitted(&state, upstream, &upstream_name, &parsed)
+            .await
+            .map_err(|status| format!("Failed to evaluate admission, {status}"))?;
+
+    Ok(Verdict {
+        image: image.to_string(),
+        digest: parsed.digest,
+        admitted: admitted.is_ok(),
+        message: admitted.err().map(|error| error.to_string()),
+        vulnerabilities: scan.body.projects.first().map(|project| Vulnerabilities {
+            critical: project.issue_counts_by_severity.critical,
+            high: project.issue_counts_by_severity.high,
+            medium: project.issue_counts_by_severity.medium,
+            low: project.issue_counts_by_severity.low,
+        }),
+    })
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = if self.admitted { "admitted" } else { "denied" };
+
+        write!(f, "{}: {verdict}", self.image)?;
+
+        if let Some(message) = &self.message {
+            write!(f, " ({message})")?;
+        }
+
+        writeln!(f)?;
+
+        if let Some(digest) = &self.digest {
+            writeln!(f, "  digest:   {digest}")?;
+        }
+
+        if let Some(vulnerabilities) = &self.vulnerabilities {
+            writeln!(f, "  critical: {}", vulnerabilities.critical)?;
+            writeln!(f, "  high:     {}", vulnerabilities.high)?;
+            writeln!(f, "  medium:   {}", vulnerabilities.medium)?;
+            writeln!(f, "  low:      {}", vulnerabilities.low)?;
+        }
+
+        Ok(())
+    }
+}
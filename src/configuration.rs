@@ -1,49 +1,884 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
 use config::{Config, Environment, File};
 
-#[derive(Clone, serde::Deserialize)]
+pub use crate::secret::Secret;
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Configuration {
+    #[serde(default)]
+    pub admission: Admission,
+    pub attestation: Option<Attestation>,
+    pub break_glass: Option<BreakGlass>,
+    pub cache: Option<Cache>,
+    pub delete: Option<Delete>,
+    pub enforcement: Option<Enforcement>,
     pub http_server: HttpServer,
+    pub kubernetes: Option<Kubernetes>,
+    pub metadata: Option<Metadata>,
+    pub notification: Option<Notification>,
     pub oci: Oci,
+    pub push: Option<Push>,
+    pub quarantine: Option<Quarantine>,
+    pub registry_events: Option<RegistryEvents>,
+    pub signature: Option<Signature>,
     pub snyk: Snyk,
+    pub vulnerability: Option<Vulnerability>,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct Admission {
+    #[serde(default)]
+    pub index: AdmissionIndex,
+}
+
+/// Admission of multi platform images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdmissionIndex {
+    /// The image index is admitted as a whole, platform manifests inherit its decision.
+    #[default]
+    Index,
+    /// Each platform manifest is admitted individually, the image index is not evaluated.
+    Platform,
 }
 
-#[derive(Clone, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Attestation {
+    #[serde(default)]
+    pub require_provenance: bool,
+    #[serde(default)]
+    pub require_sbom: bool,
+    #[serde(default)]
+    pub builder_ids: Vec<String>,
+    #[serde(default)]
+    pub source_repositories: Vec<String>,
+    #[serde(default)]
+    pub branches: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct BreakGlass {
+    pub secret: Option<Secret>,
+    pub secret_file: Option<String>,
+    pub admin_token: Option<Secret>,
+    pub admin_token_file: Option<String>,
+    #[serde(default = "BreakGlass::default_ttl")]
+    pub ttl: u64,
+    #[serde(default = "BreakGlass::default_max_ttl")]
+    pub max_ttl: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Cache {
+    pub directory: String,
+    pub max_size: u64,
+    #[serde(default = "Cache::default_manifest_ttl")]
+    pub manifest_ttl: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Delete {
+    pub journal: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<DeleteRule>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct DeleteRule {
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    #[serde(default)]
+    pub action: DeleteAction,
+    #[serde(default)]
+    pub principals: Vec<String>,
+}
+
+/// Handling of deletions of manifests and blobs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteAction {
+    /// The deletion is forwarded to the upstream.
+    #[default]
+    Allow,
+    /// The deletion is refused.
+    Deny,
+    /// The deletion is recorded in the journal and hidden from pulls, but not forwarded to the
+    /// upstream, so it can be restored.
+    Soft,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Enforcement {
+    #[serde(default)]
+    pub rules: Vec<EnforcementRule>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct EnforcementRule {
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    pub mode: EnforcementMode,
+}
+
+/// Enforcement of admission denials on pulls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnforcementMode {
+    /// The pull is denied.
+    #[default]
+    Enforce,
+    /// The pull is proxied with a warning header, and the denial is recorded.
+    Warn,
+    /// The pull is proxied, and the denial is only recorded.
+    Audit,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct HttpServer {
     pub host: String,
     pub port: u16,
+    pub tls: Option<Tls>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Kubernetes {
+    pub gateway: Option<String>,
+    #[serde(default)]
+    pub registries: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Metadata {
+    #[serde(default)]
+    pub required_labels: Vec<String>,
+    #[serde(default)]
+    pub base_images: Vec<String>,
+    pub max_age_days: Option<u64>,
+    #[serde(default)]
+    pub deny_root: bool,
+    pub allowed_ports: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Notification {
+    #[serde(default = "Notification::default_scan_interval")]
+    pub scan_interval: u64,
+    #[serde(default = "Notification::default_scan_timeout")]
+    pub scan_timeout: u64,
+    #[serde(default)]
+    pub targets: Vec<NotificationTarget>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct NotificationTarget {
+    pub url: Secret,
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    #[serde(default)]
+    pub events: Vec<NotificationEvent>,
+    pub template: Option<String>,
+    #[serde(default = "NotificationTarget::default_deduplication_window")]
+    pub deduplication_window: u64,
+    #[serde(default = "NotificationTarget::default_retries")]
+    pub retries: u32,
+}
+
+/// Events notified to webhooks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// A pull was denied by the admission policy.
+    Denied,
+    /// A manifest was pushed.
+    Pushed,
+    /// The scan of a pushed manifest found critical vulnerabilities.
+    Critical,
+    /// A break-glass token was issued, or used to bypass the admission policy.
+    BreakGlass,
 }
 
-#[derive(Clone, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Oci {
     pub base_address: String,
+    #[serde(default)]
+    pub mirror: bool,
+    pub registry: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub password_file: Option<String>,
+    #[serde(default)]
+    pub upstreams: Vec<OciUpstream>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct OciUpstream {
+    pub prefix: Option<String>,
+    pub registry: Option<String>,
+    pub base_address: String,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub password_file: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Push {
+    #[serde(default)]
+    pub immutability: Vec<PushImmutability>,
+    #[serde(default)]
+    pub tag_patterns: Vec<String>,
+    #[serde(default)]
+    pub media_types: Vec<String>,
+    #[serde(default)]
+    pub deny_foreign_layers: bool,
+    pub max_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PushImmutability {
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub principals: Vec<String>,
 }
 
-#[derive(Clone, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Quarantine {
+    #[serde(default)]
+    pub wait: u64,
+    #[serde(default = "Quarantine::default_poll_interval")]
+    pub poll_interval: u64,
+    #[serde(default = "Quarantine::default_expiry")]
+    pub expiry: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct RegistryEvents {
+    pub secret: Option<Secret>,
+    pub secret_file: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Signature {
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Snyk {
-    pub api_key: String,
+    pub api_key: Option<Secret>,
+    pub api_key_file: Option<String>,
     pub base_address: String,
     pub integration_id: String,
     pub organization_id: String,
 }
 
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Tls {
+    pub certificate: String,
+    pub key: String,
+    pub client_ca: Option<String>,
+    #[serde(default)]
+    pub client_certificate_required: bool,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Vulnerability {
+    #[serde(default)]
+    pub critical: u32,
+    #[serde(default)]
+    pub high: u32,
+    #[serde(default)]
+    pub medium: u32,
+    #[serde(default)]
+    pub low: u32,
+}
+
+impl BreakGlass {
+    fn default_ttl() -> u64 {
+        3600
+    }
+
+    fn default_max_ttl() -> u64 {
+        86400
+    }
+}
+
+impl Cache {
+    fn default_manifest_ttl() -> u64 {
+        300
+    }
+}
+
+impl Notification {
+    fn default_scan_interval() -> u64 {
+        30
+    }
+
+    fn default_scan_timeout() -> u64 {
+        1800
+    }
+}
+
+impl NotificationTarget {
+    fn default_deduplication_window() -> u64 {
+        300
+    }
+
+    fn default_retries() -> u32 {
+        3
+    }
+}
+
+impl Quarantine {
+    fn default_poll_interval() -> u64 {
+        5
+    }
+
+    fn default_expiry() -> u64 {
+        3600
+    }
+}
+
+/// The problems found by validating a configuration.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub problems: Vec<String>,
+}
+
+impl Configuration {
+    /// Validates the configuration, reporting every problem found rather than only the first.
+    ///
+    /// # Errors
+    ///
+    /// If a required value is empty, an address is not an HTTP URL, options conflict, a pattern
+    /// or public key is malformed, or a secret file cannot be read, an error is returned.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut problems = Vec::new();
+
+        required(&mut problems, "http_server.host", &self.http_server.host);
+
+        if let Some(tls) = &self.http_server.tls {
+            required(
+                &mut problems,
+                "http_server.tls.certificate",
+                &tls.certificate,
+            );
+            required(&mut problems, "http_server.tls.key", &tls.key);
+        }
+
+        secret(
+            &mut problems,
+            "snyk.api_key",
+            self.snyk.api_key.as_ref(),
+            self.snyk.api_key_file.as_ref(),
+        );
+        required(
+            &mut problems,
+            "snyk.integration_id",
+            &self.snyk.integration_id,
+        );
+        required(
+            &mut problems,
+            "snyk.organization_id",
+            &self.snyk.organization_id,
+        );
+
+        if let Some(break_glass) = &self.break_glass {
+            secret(
+                &mut problems,
+                "break_glass.secret",
+                break_glass.secret.as_ref(),
+                break_glass.secret_file.as_ref(),
+            );
+            secret(
+                &mut problems,
+                "break_glass.admin_token",
+                break_glass.admin_token.as_ref(),
+                break_glass.admin_token_file.as_ref(),
+            );
+        }
+
+        if let Some(registry_events) = &self.registry_events {
+            secret(
+                &mut problems,
+                "registry_events.secret",
+                registry_events.secret.as_ref(),
+                registry_events.secret_file.as_ref(),
+            );
+        }
+
+        credentials(
+            &mut problems,
+            "oci",
+            self.oci.username.as_ref(),
+            self.oci.password.as_ref(),
+            self.oci.password_file.as_ref(),
+        );
+
+        for (index, upstream) in self.oci.upstreams.iter().enumerate() {
+            credentials(
+                &mut problems,
+                &format!("oci.upstreams[{index}]"),
+                upstream.username.as_ref(),
+                upstream.password.as_ref(),
+                upstream.password_file.as_ref(),
+            );
+        }
+
+        let mut url = |key: &str, value: &str| {
+            if !is_http_url(value) {
+                problems.push(format!("{key} must be an HTTP URL, got {value:?}"));
+            }
+        };
+
+        url("oci.base_address", &self.oci.base_address);
+        url("snyk.base_address", &self.snyk.base_address);
+
+        for (index, upstream) in self.oci.upstreams.iter().enumerate() {
+            url(
+                &format!("oci.upstreams[{index}].base_address"),
+                &upstream.base_address,
+            );
+        }
+
+        if let Some(notification) = &self.notification {
+            for (index, target) in notification.targets.iter().enumerate() {
+                url(
+                    &format!("notification.targets[{index}].url"),
+                    target.url.expose(),
+                );
+            }
+        }
+
+        if let Some(gateway) = self.kubernetes.as_ref().and_then(|k| k.gateway.as_ref()) {
+            if gateway.contains("://") || gateway.contains('/') {
+                problems.push(format!(
+                    "kubernetes.gateway must be a registry host, got {gateway:?}"
+                ));
+            }
+        }
+
+        self.validate_options(&mut problems);
+        self.validate_patterns(&mut problems);
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { problems })
+        }
+    }
+
+    fn validate_options(&self, problems: &mut Vec<String>) {
+        let mut prefixes = BTreeSet::new();
+
+        for (index, upstream) in self.oci.upstreams.iter().enumerate() {
+            match &upstream.prefix {
+                Some(prefix) if !prefixes.insert(prefix) => {
+                    problems.push(format!(
+                        "oci.upstreams[{index}].prefix {prefix:?} is already used"
+                    ));
+                }
+                None if upstream.registry.is_none() || !self.oci.mirror => {
+                    problems.push(format!(
+                        "oci.upstreams[{index}] requires a prefix, or a registry in mirror mode"
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if self
+            .http_server
+            .tls
+            .as_ref()
+            .is_some_and(|tls| tls.client_certificate_required && tls.client_ca.is_none())
+        {
+            problems.push(
+                "http_server.tls.client_certificate_required requires http_server.tls.client_ca"
+                    .to_string(),
+            );
+        }
+
+        if self.attestation.is_some() && self.signature.is_none() {
+            problems.push("attestation requires signature.public_keys".to_string());
+        }
+
+        if self
+            .signature
+            .as_ref()
+            .is_some_and(|signature| signature.public_keys.is_empty())
+        {
+            problems.push("signature.public_keys must not be empty".to_string());
+        }
+
+        if let Some(break_glass) = &self.break_glass {
+            if break_glass.ttl > break_glass.max_ttl {
+                problems.push("break_glass.ttl must not exceed break_glass.max_ttl".to_string());
+            }
+
+            let reused = break_glass
+                .secret
+                .as_ref()
+                .zip(break_glass.admin_token.as_ref())
+                .is_some_and(|(secret, admin_token)| {
+                    !secret.expose().is_empty() && secret.expose() == admin_token.expose()
+                });
+
+            if reused {
+                problems
+                    .push("break_glass.secret and break_glass.admin_token must differ".to_string());
+            }
+        }
+
+        if self.cache.as_ref().is_some_and(|cache| cache.max_size == 0) {
+            problems.push("cache.max_size must be positive".to_string());
+        }
+
+        if let Some(quarantine) = &self.quarantine {
+            if quarantine.wait > 0 && quarantine.poll_interval == 0 {
+                problems.push("quarantine.poll_interval must be positive".to_string());
+            }
+
+            if quarantine.wait > quarantine.expiry {
+                problems.push("quarantine.wait must not exceed quarantine.expiry".to_string());
+            }
+        }
+
+        // soft deletions hide content from every client, so they are limited to named principals.
+        for (index, rule) in self
+            .delete
+            .iter()
+            .flat_map(|delete| &delete.rules)
+            .enumerate()
+        {
+            if rule.action == DeleteAction::Soft && rule.principals.is_empty() {
+                problems.push(format!(
+                    "delete.rules[{index}].principals must not be empty for soft deletions"
+                ));
+            }
+        }
+    }
+
+    /// Compiles the patterns and public keys, as the policies do when the server starts.
+    fn validate_patterns(&self, problems: &mut Vec<String>) {
+        for (index, rule) in self
+            .delete
+            .iter()
+            .flat_map(|delete| &delete.rules)
+            .enumerate()
+        {
+            compiles(
+                problems,
+                &format!("delete.rules[{index}].repositories"),
+                crate::pattern::globs(&rule.repositories),
+            );
+        }
+
+        for (index, rule) in self
+            .enforcement
+            .iter()
+            .flat_map(|enforcement| &enforcement.rules)
+            .enumerate()
+        {
+            compiles(
+                problems,
+                &format!("enforcement.rules[{index}].repositories"),
+                crate::pattern::globs(&rule.repositories),
+            );
+        }
+
+        for (index, target) in self
+            .notification
+            .iter()
+            .flat_map(|notification| &notification.targets)
+            .enumerate()
+        {
+            compiles(
+                problems,
+                &format!("notification.targets[{index}].repositories"),
+                crate::pattern::globs(&target.repositories),
+            );
+        }
+
+        for (index, immutability) in self
+            .push
+            .iter()
+            .flat_map(|push| &push.immutability)
+            .enumerate()
+        {
+            compiles(
+                problems,
+                &format!("push.immutability[{index}].repositories"),
+                crate::pattern::globs(&immutability.repositories),
+            );
+            compiles(
+                problems,
+                &format!("push.immutability[{index}].tags"),
+                crate::pattern::anchored(&immutability.tags),
+            );
+        }
+
+        if let Some(push) = &self.push {
+            compiles(
+                problems,
+                "push.tag_patterns",
+                crate::pattern::anchored(&push.tag_patterns),
+            );
+        }
+
+        if let Some(signature) = &self.signature {
+            compiles(
+                problems,
+                "signature.public_keys",
+                crate::signature::Verifier::new(signature.public_keys.iter().map(String::as_str)),
+            );
+        }
+    }
+
+    /// Returns the configuration with the values of secrets replaced, suitable for display.
+    ///
+    /// # Errors
+    ///
+    /// If the configuration cannot be serialized, an error is returned.
+    pub fn redacted(&self) -> crate::Result<serde_json::Value> {
+        // secrets are always serialized redacted.
+        serde_json::to_value(self).map_err(Into::into)
+    }
+
+    /// Returns the secrets of the configuration, by the key of their value.
+    fn secrets(&self) -> BTreeMap<String, &Secret> {
+        let mut secrets = vec![
+            ("snyk.api_key".to_string(), self.snyk.api_key.as_ref()),
+            ("oci.password".to_string(), self.oci.password.as_ref()),
+        ];
+
+        for (index, upstream) in self.oci.upstreams.iter().enumerate() {
+            secrets.push((
+                format!("oci.upstreams.{index}.password"),
+                upstream.password.as_ref(),
+            ));
+        }
+
+        if let Some(break_glass) = &self.break_glass {
+            secrets.push((
+                "break_glass.secret".to_string(),
+                break_glass.secret.as_ref(),
+            ));
+            secrets.push((
+                "break_glass.admin_token".to_string(),
+                break_glass.admin_token.as_ref(),
+            ));
+        }
+
+        if let Some(registry_events) = &self.registry_events {
+            secrets.push((
+                "registry_events.secret".to_string(),
+                registry_events.secret.as_ref(),
+            ));
+        }
+
+        if let Some(notification) = &self.notification {
+            for (index, target) in notification.targets.iter().enumerate() {
+                secrets.push((
+                    format!("notification.targets.{index}.url"),
+                    Some(&target.url),
+                ));
+            }
+        }
+
+        secrets
+            .into_iter()
+            .filter_map(|(key, secret)| Some((key, secret?)))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration")?;
+
+        for problem in &self.problems {
+            write!(f, "\n  - {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks the required `value` of the `key` is not empty.
+fn required(problems: &mut Vec<String>, key: &str, value: &str) {
+    if value.trim().is_empty() {
+        problems.push(format!("{key} is required"));
+    }
+}
+
+/// Checks the secret of the `key` is given, either as its `value` or its `file`.
+fn secret(problems: &mut Vec<String>, key: &str, value: Option<&Secret>, file: Option<&String>) {
+    match (value, file) {
+        (Some(_), Some(_)) => {
+            problems.push(format!("{key} and {key}_file are mutually exclusive"));
+        }
+        (Some(value), None) => required(problems, key, value.expose()),
+        (None, Some(file)) if file.trim().is_empty() => {
+            required(problems, &format!("{key}_file"), file);
+        }
+        (None, Some(file)) => {
+            if let Err(error) = crate::secret::Source::new(None, Some(file)) {
+                problems.push(format!("{key}_file cannot be read, {error}"));
+            }
+        }
+        (None, None) => problems.push(format!("{key} or {key}_file is required")),
+    }
+}
+
+/// Checks the value of the `key` compiles, as when the server starts.
+fn compiles<T>(problems: &mut Vec<String>, key: &str, compiled: crate::Result<T>) {
+    if let Err(error) = compiled {
+        problems.push(format!("{key} is malformed, {error}"));
+    }
+}
+
+/// Checks the password of the credentials of the `key` is given with their `username`.
+fn credentials(
+    problems: &mut Vec<String>,
+    key: &str,
+    username: Option<&String>,
+    password: Option<&Secret>,
+    password_file: Option<&String>,
+) {
+    match username {
+        Some(username) => {
+            required(problems, &format!("{key}.username"), username);
+            secret(
+                problems,
+                &format!("{key}.password"),
+                password,
+                password_file,
+            );
+        }
+        None if password.is_some() || password_file.is_some() => {
+            problems.push(format!("{key}.password requires {key}.username"));
+        }
+        None => {}
+    }
+}
+
+/// Checks the value is an absolute HTTP or HTTPS URL.
+fn is_http_url(value: &str) -> bool {
+    value.parse::<hyper::Uri>().is_ok_and(|uri| {
+        matches!(uri.scheme_str(), Some("http" | "https")) && uri.authority().is_some()
+    })
+}
+
+/// Returns the paths of the config files, in any of the supported formats.
+pub(crate) fn files() -> Vec<std::path::PathBuf> {
+    ["ini", "json", "json5", "ron", "toml", "yaml", "yml"]
+        .iter()
+        .map(|extension| format!("config.{extension}").into())
+        .collect()
+}
+
+/// Returns the keys whose values differ between the configurations.
+///
+/// Values are not returned, as they may be secrets.
+pub(crate) fn diff(
+    previous: &Configuration,
+    current: &Configuration,
+) -> crate::Result<Vec<String>> {
+    fn flatten(
+        prefix: &str,
+        value: serde_json::Value,
+        keys: &mut BTreeMap<String, serde_json::Value>,
+    ) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object {
+                    flatten(&format!("{prefix}{key}."), value, keys);
+                }
+            }
+            serde_json::Value::Array(array) => {
+                for (index, value) in array.into_iter().enumerate() {
+                    flatten(&format!("{prefix}{index}."), value, keys);
+                }
+            }
+            value => {
+                keys.insert(prefix.trim_end_matches('.').to_string(), value);
+            }
+        }
+    }
+
+    let mut previous_keys = BTreeMap::new();
+    let mut current_keys = BTreeMap::new();
+    flatten("", serde_json::to_value(previous)?, &mut previous_keys);
+    flatten("", serde_json::to_value(current)?, &mut current_keys);
+
+    // secrets are serialized redacted, so they are compared by their values.
+    let previous_secrets = previous.secrets();
+    let current_secrets = current.secrets();
+
+    let changed_secrets = previous_secrets
+        .keys()
+        .chain(current_secrets.keys())
+        .filter(|key| {
+            previous_secrets.get(*key).map(|secret| secret.expose())
+                != current_secrets.get(*key).map(|secret| secret.expose())
+        });
+
+    Ok(previous_keys
+        .keys()
+        .chain(current_keys.keys())
+        .filter(|key| previous_keys.get(*key) != current_keys.get(*key))
+        .chain(changed_secrets)
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect())
+}
+
 /// Loads the configuration from the environment variables and the config file.
 ///
 /// # Errors
 ///
-/// If the configuration file cannot be loaded, an error is returned.
+/// If the configuration file cannot be loaded, or the configuration is invalid, an error is
+/// returned.
 pub fn load(overrides: &[(&str, &str)]) -> crate::Result<Configuration> {
+    build(File::with_name("config").required(false), overrides)
+}
+
+/// Loads the configuration from the environment variables and the config file at `path`.
+///
+/// # Errors
+///
+/// If the configuration file cannot be loaded, or the configuration is invalid, an error is
+/// returned.
+pub fn load_file(path: &Path) -> crate::Result<Configuration> {
+    build(File::from(path), &[])
+}
+
+fn build<S>(file: S, overrides: &[(&str, &str)]) -> crate::Result<Configuration>
+where
+    S: config::Source + Send + Sync + 'static,
+{
     let mut config_builder = Config::builder()
         .set_default("http_server.host", "127.0.0.1")?
         .set_default("http_server.port", "80")?
-        .add_source(File::with_name("config").required(false))
+        .add_source(file)
         .add_source(Environment::with_prefix("CONTAINER_REGISTRY_GATEWAY").separator("__"));
 
     for &(key, value) in overrides {
         config_builder = config_builder.set_override(key, value)?;
     }
 
-    config_builder
-        .build()?
-        .try_deserialize()
-        .map_err(Into::into)
+    let configuration: Configuration = config_builder.build()?.try_deserialize()?;
+
+    configuration.validate()?;
+
+    Ok(configuration)
 }
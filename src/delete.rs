@@ -0,0 +1,224 @@
+use std::{
+    collections::HashSet,
+    io::{BufRead as _, Write as _},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    configuration::DeleteAction,
+    logic::AdmitError,
+    pattern::{self, Glob},
+};
+
+/// Protects repositories against deletion of their manifests and blobs.
+#[derive(Clone)]
+pub(crate) struct Policy {
+    rules: Vec<Rule>,
+    journal: Option<PathBuf>,
+    deleted: Arc<Mutex<HashSet<String>>>,
+}
+
+#[derive(Clone)]
+struct Rule {
+    repositories: Vec<Glob>,
+    action: DeleteAction,
+    principals: Vec<String>,
+}
+
+/// The way a deletion is handled.
+pub(crate) enum Decision {
+    /// The deletion is forwarded to the upstream.
+    Forward,
+    /// The deletion is recorded by the gateway, and is not forwarded to the upstream.
+    Soft,
+    /// The deletion is refused.
+    Deny(AdmitError),
+}
+
+/// An entry of the soft delete journal.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct JournalEntry {
+    repository: String,
+    reference: String,
+    principal: Option<String>,
+    /// Whether the entry restores a previous soft deletion.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    restored: bool,
+}
+
+/// Request to restore soft deleted content.
+#[derive(serde::Deserialize)]
+pub(crate) struct RestoreRequest {
+    /// Name of the repository, as pulled through the gateway.
+    pub(crate) repository: String,
+    pub(crate) reference: String,
+}
+
+impl Policy {
+    /// Creates a new `Policy` instance.
+    ///
+    /// Soft deletions recorded in the journal by a previous run are reapplied, unless restored
+    /// since.
+    ///
+    /// # Errors
+    ///
+    /// If any of the repository patterns is malformed, or the journal can not be read, an error
+    /// is returned.
+    pub(crate) fn new(configuration: crate::configuration::Delete) -> crate::Result<Policy> {
+        let mut deleted = HashSet::new();
+        let journal = configuration.journal.map(PathBuf::from);
+
+        if let Some(journal) = journal.as_ref().filter(|journal| journal.exists()) {
+            for line in std::io::BufReader::new(std::fs::File::open(journal)?).lines() {
+                let entry = serde_json::from_str::<JournalEntry>(&line?)?;
+                let key = key(&entry.repository, &entry.reference);
+
+                if entry.restored {
+                    deleted.remove(&key);
+                } else {
+                    deleted.insert(key);
+                }
+            }
+        }
+
+        Ok(Policy {
+            rules: configuration
+                .rules
+                .iter()
+                .map(|rule| {
+                    Ok(Rule {
+                        repositories: pattern::globs(&rule.repositories)?,
+                        action: rule.action,
+                        principals: rule.principals.clone(),
+                    })
+                })
+                .collect::<crate::Result<_>>()?,
+            journal,
+            deleted: Arc::new(Mutex::new(deleted)),
+        })
+    }
+
+    /// Shares the soft deletions recorded by the `previous` instance, so they survive reloads.
+    pub(crate) fn carry_over(&mut self, previous: &Policy) {
+        let deleted = std::mem::take(&mut *self.deleted.lock().unwrap());
+        previous.deleted.lock().unwrap().extend(deleted);
+        self.deleted = previous.deleted.clone();
+    }
+
+    /// Decides how the deletion of content of the repository `name` by the `principal` is
+    /// handled.
+    ///
+    /// The first rule matching the repository applies, and deletions of repositories without a
+    /// matching rule are forwarded.
+    pub(crate) fn decide(&self, name: &str, principal: Option<&str>) -> Decision {
+        let Some(rule) = self.rules.iter().find(|rule| {
+            rule.repositories.is_empty() || rule.repositories.iter().any(|glob| glob.is_match(name))
+        }) else {
+            return Decision::Forward;
+        };
+
+        let authorized = rule.principals.is_empty()
+            || principal.is_some_and(|principal| {
+                rule.principals
+                    .iter()
+                    .any(|authorized| authorized == principal)
+            });
+
+        match (rule.action, authorized) {
+            (DeleteAction::Deny, _) | (_, false) => Decision::Deny(AdmitError::DeleteDenied),
+            (DeleteAction::Allow, true) => Decision::Forward,
+            (DeleteAction::Soft, true) => Decision::Soft,
+        }
+    }
+
+    /// Records the soft deletion of the `reference` of the `repository`.
+    ///
+    /// # Errors
+    ///
+    /// If the journal can not be written, an error is returned.
+    pub(crate) fn soft_delete(
+        &self,
+        repository: &str,
+        reference: &str,
+        principal: Option<&str>,
+    ) -> crate::Result<()> {
+        let mut deleted = self.deleted.lock().unwrap();
+
+        self.append(repository, reference, principal, false)?;
+        deleted.insert(key(repository, reference));
+
+        Ok(())
+    }
+
+    /// Restores the soft deleted `reference` of the `repository`, returning whether it was soft
+    /// deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the journal can not be written, an error is returned.
+    pub(crate) fn restore(
+        &self,
+        repository: &str,
+        reference: &str,
+        principal: Option<&str>,
+    ) -> crate::Result<bool> {
+        let mut deleted = self.deleted.lock().unwrap();
+        let key = key(repository, reference);
+
+        if !deleted.contains(&key) {
+            return Ok(false);
+        }
+
+        self.append(repository, reference, principal, true)?;
+        deleted.remove(&key);
+
+        Ok(true)
+    }
+
+    /// Appends an entry to the journal, if any.
+    fn append(
+        &self,
+        repository: &str,
+        reference: &str,
+        principal: Option<&str>,
+        restored: bool,
+    ) -> crate::Result<()> {
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+
+        let mut line = serde_json::to_vec(&JournalEntry {
+            repository: repository.to_string(),
+            reference: reference.to_string(),
+            principal: principal.map(ToString::to_string),
+            restored,
+        })?;
+        line.push(b'\n');
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal)?
+            .write_all(&line)?;
+
+        Ok(())
+    }
+
+    /// Checks if any of the `references` of the `repository` has been soft deleted.
+    pub(crate) fn is_deleted<'a>(
+        &self,
+        repository: &str,
+        references: impl IntoIterator<Item = &'a str>,
+    ) -> bool {
+        let deleted = self.deleted.lock().unwrap();
+
+        references
+            .into_iter()
+            .any(|reference| deleted.contains(&key(repository, reference)))
+    }
+}
+
+fn key(repository: &str, reference: &str) -> String {
+    format!("{repository}@{reference}")
+}
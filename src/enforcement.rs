@@ -0,0 +1,52 @@
+use crate::{
+    configuration::EnforcementMode,
+    pattern::{self, Glob},
+};
+
+/// Selects how admission denials are enforced for each repository.
+#[derive(Clone)]
+pub(crate) struct Policy {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+struct Rule {
+    repositories: Vec<Glob>,
+    mode: EnforcementMode,
+}
+
+impl Policy {
+    /// Creates a new `Policy` instance.
+    ///
+    /// # Errors
+    ///
+    /// If any of the repository patterns is malformed, an error is returned.
+    pub(crate) fn new(configuration: &crate::configuration::Enforcement) -> crate::Result<Policy> {
+        Ok(Policy {
+            rules: configuration
+                .rules
+                .iter()
+                .map(|rule| {
+                    Ok(Rule {
+                        repositories: pattern::globs(&rule.repositories)?,
+                        mode: rule.mode,
+                    })
+                })
+                .collect::<crate::Result<_>>()?,
+        })
+    }
+
+    /// Returns the enforcement mode of the repository `name`.
+    ///
+    /// The first rule matching the repository applies, rules without repository patterns match
+    /// every repository. Repositories matching no rule are enforced.
+    pub(crate) fn mode(&self, name: &str) -> EnforcementMode {
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.repositories.is_empty()
+                    || rule.repositories.iter().any(|glob| glob.is_match(name))
+            })
+            .map_or(EnforcementMode::Enforce, |rule| rule.mode)
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use crate::{configuration, logic, snyk::organization_projects_post};
+
+/// Outcome of evaluating a policy against fixture scan results.
+#[derive(Debug, serde::Serialize)]
+pub struct Report {
+    pub fixtures: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// A fixture whose verdict differs from the expected verdict.
+#[derive(Debug, serde::Serialize)]
+pub struct Mismatch {
+    pub fixture: String,
+    pub expected: Verdict,
+    pub actual: Verdict,
+}
+
+/// Verdict of the admission policy for a scan result.
+///
+/// Expected verdicts without a message match any message.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct Verdict {
+    pub admitted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct Fixture {
+    scan: Scan,
+    expected: Verdict,
+}
+
+/// Scan result, either as returned by the projects endpoint or as a single project.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Scan {
+    Projects(organization_projects_post::ResponseBody),
+    Project(organization_projects_post::ResponseBodyProject),
+}
+
+#[derive(serde::Deserialize)]
+struct Policy {
+    vulnerability: Option<configuration::Vulnerability>,
+}
+
+/// Evaluates the vulnerability policy of the configuration file `policy` against every JSON
+/// fixture in the `fixtures` directory.
+///
+/// Each fixture holds a `scan` result and the `expected` verdict.
+///
+/// # Errors
+///
+/// If the policy or a fixture cannot be read, an error is returned.
+pub fn evaluate(policy: &Path, fixtures: &Path) -> crate::Result<Report> {
+    let policy: Policy = config::Config::builder()
+        .add_source(config::File::from(policy))
+        .build()?
+        .try_deserialize()?;
+
+    let thresholds = policy
+        .vulnerability
+        .as_ref()
+        .map(logic::Thresholds::from)
+        .unwrap_or_default();
+
+    let mut paths = std::fs::read_dir(fixtures)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "json")
+    });
+    paths.sort();
+
+    let mut report = Report {
+        fixtures: paths.len(),
+        mismatches: Vec::new(),
+    };
+
+    for path in paths {
+        let fixture: Fixture = serde_json::from_slice(&std::fs::read(&path)?)
+            .map_err(|error| format!("Failed to parse fixture {}, {error}", path.display()))?;
+
+        let response = organization_projects_post::Response {
+            body: match fixture.scan {
+                Scan::Projects(body) => body,
+                Scan::Project(project) => organization_projects_post::ResponseBody {
+                    projects: vec![project],
+                },
+            },
+        };
+
+        let admitted = logic::admitted(&response, &thresholds);
+        let actual = Verdict {
+            admitted: admitted.is_ok(),
+            message: admitted.err().map(|error| error.to_string()),
+        };
+
+        let matches = fixture.expected.admitted == actual.admitted
+            && fixture
+                .expected
+                .message
+                .as_ref()
+                .is_none_or(|message| actual.message.as_ref() == Some(message));
+
+        if !matches {
+            report.mismatches.push(Mismatch {
+                fixture: path.display().to_string(),
+                expected: fixture.expected,
+                actual,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", if self.admitted { "admitted" } else { "denied" })?;
+
+        if let Some(message) = &self.message {
+            write!(f, " ({message})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "{}: expected {}, got {}",
+                mismatch.fixture, mismatch.expected, mismatch.actual
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{} fixtures, {} mismatches",
+            self.fixtures,
+            self.mismatches.len()
+        )
+    }
+}
@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+
+/// Kubernetes admission webhooks, rejecting workloads referencing images denied by the gateway and
+/// pinning the others to their admitted digest.
+#[derive(Clone)]
+pub(crate) struct Webhook {
+    gateway: Option<String>,
+    registries: Vec<String>,
+}
+
+/// An `admission.k8s.io/v1` `AdmissionReview`.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdmissionReview {
+    pub(crate) api_version: String,
+    pub(crate) kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) request: Option<AdmissionRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) response: Option<AdmissionResponse>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdmissionRequest {
+    pub(crate) uid: String,
+    pub(crate) kind: GroupVersionKind,
+    #[serde(default)]
+    pub(crate) object: serde_json::Value,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct GroupVersionKind {
+    #[serde(default)]
+    pub(crate) group: String,
+    #[serde(default)]
+    pub(crate) version: String,
+    pub(crate) kind: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdmissionResponse {
+    pub(crate) uid: String,
+    pub(crate) allowed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) status: Option<AdmissionStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) patch_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) patch: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct AdmissionStatus {
+    pub(crate) code: u16,
+    pub(crate) message: String,
+}
+
+/// A JSON Patch operation replacing the image of a container.
+#[derive(serde::Serialize)]
+pub(crate) struct Patch {
+    op: &'static str,
+    path: String,
+    value: String,
+}
+
+/// A container of a Pod or a workload's Pod template.
+pub(crate) struct Container {
+    /// JSON Pointer to the image of the container.
+    pub(crate) path: String,
+    pub(crate) image: String,
+}
+
+/// A container image reference, as `[registry/]repository[:tag][@digest]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Image {
+    pub(crate) registry: Option<String>,
+    pub(crate) repository: String,
+    pub(crate) tag: Option<String>,
+    pub(crate) digest: Option<String>,
+}
+
+impl Webhook {
+    /// Creates a new `Webhook` instance.
+    pub(crate) fn new(configuration: &crate::configuration::Kubernetes) -> Webhook {
+        Webhook {
+            gateway: configuration.gateway.clone(),
+            registries: configuration.registries.clone(),
+        }
+    }
+
+    /// Rewrites the image to be pulled through the gateway.
+    ///
+    /// Images already referencing the gateway keep their repository, other images are mapped to
+    /// the prefix of the upstream serving their registry.
+    ///
+    /// Returns `None` if the image cannot be pulled through the gateway by name, as for upstreams
+    /// only selected through the `ns` query parameter.
+    pub(crate) fn rewrite(&self, proxy: &crate::oci::Proxy, image: Image) -> Option<Image> {
+        if self.is_gateway(&image) {
+            return Some(Image {
+                registry: self.gateway.clone().or(image.registry),
+                ..image
+            });
+        }
+
+        Some(Image {
+            repository: proxy.name(
+                image.registry.as_deref().unwrap_or("docker.io"),
+                &image.repository,
+            )?,
+            registry: Some(self.gateway.clone()?),
+            ..image
+        })
+    }
+
+    /// Resolves the upstream the image is pulled from through the gateway, to be evaluated.
+    ///
+    /// Images referencing the gateway or its registries are resolved by their repository.
+    /// Without registries, other images are resolved through the upstream serving their
+    /// registry.
+    ///
+    /// Returns `None` if the image is not evaluated, or an error if no upstream serves its
+    /// registry.
+    pub(crate) fn upstream<'p, 'i>(
+        &self,
+        proxy: &'p crate::oci::Proxy,
+        image: &'i Image,
+    ) -> Option<crate::Result<(&'p crate::oci::Upstream, Cow<'i, str>)>> {
+        if self.is_gateway(image) {
+            return Some(Ok(proxy.resolve(&image.repository, None)));
+        }
+
+        if !self.registries.is_empty() {
+            return None;
+        }
+
+        let registry = image.registry.as_deref().unwrap_or("docker.io");
+
+        Some(
+            proxy
+                .resolve_registry(registry, &image.repository)
+                .ok_or_else(|| format!("Registry {registry} is not served by the gateway").into()),
+        )
+    }
+
+    /// Checks if the image references the gateway or one of its registries.
+    fn is_gateway(&self, image: &Image) -> bool {
+        image.registry.as_ref().is_some_and(|registry| {
+            self.gateway.as_ref() == Some(registry) || self.registries.contains(registry)
+        })
+    }
+}
+
+impl AdmissionReview {
+    /// Creates the review answering the request `uid`.
+    ///
+    /// Requests are denied with the `messages` of the images which were not admitted.
+    pub(crate) fn response(uid: String, messages: &[String]) -> AdmissionReview {
+        AdmissionReview {
+            api_version: "admission.k8s.io/v1".to_string(),
+            kind: "AdmissionReview".to_string(),
+            request: None,
+            response: Some(AdmissionResponse {
+                uid,
+                allowed: messages.is_empty(),
+                status: (!messages.is_empty()).then(|| AdmissionStatus {
+                    code: 403,
+                    message: messages.join("; "),
+                }),
+                patch_type: None,
+                patch: None,
+            }),
+        }
+    }
+
+    /// Creates the review allowing the request `uid`, once the JSON `patch` is applied.
+    ///
+    /// # Errors
+    ///
+    /// If the patch cannot be serialized, an error is returned.
+    pub(crate) fn patched(uid: String, patch: &[Patch]) -> crate::Result<AdmissionReview> {
+        use base64::Engine as _;
+
+        let mut review = AdmissionReview::response(uid, &[]);
+
+        if let (Some(response), false) = (&mut review.response, patch.is_empty()) {
+            response.patch_type = Some("JSONPatch".to_string());
+            response.patch =
+                Some(base64::engine::general_purpose::STANDARD.encode(serde_json::to_vec(patch)?));
+        }
+
+        Ok(review)
+    }
+}
+
+impl Image {
+    /// Parses an image reference.
+    ///
+    /// As with the container runtime, the first component is the registry only if it looks like a
+    /// host name, and images without a tag or digest reference the `latest` tag.
+    pub(crate) fn parse(reference: &str) -> Image {
+        let (reference, digest) = match reference.split_once('@') {
+            Some((reference, digest)) => (reference, Some(digest.to_string())),
+            None => (reference, None),
+        };
+
+        let (reference, tag) = match reference.rsplit_once(':') {
+            Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag.to_string())),
+            _ => (reference, None),
+        };
+
+        let (registry, repository) = match reference.split_once('/') {
+            Some((registry, repository))
+                if registry.contains(['.', ':']) || registry == "localhost" =>
+            {
+                (Some(registry.to_string()), repository)
+            }
+            _ => (None, reference),
+        };
+
+        Image {
+            registry,
+            repository: repository.to_string(),
+            tag: tag.or_else(|| digest.is_none().then(|| "latest".to_string())),
+            digest,
+        }
+    }
+}
+
+impl std::fmt::Display for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(registry) = &self.registry {
+            write!(f, "{registry}/")?;
+        }
+
+        write!(f, "{}", self.repository)?;
+
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Patch {
+    /// Creates the operation replacing the image of the container.
+    pub(crate) fn image(container: &Container, image: &Image) -> Patch {
+        Patch {
+            op: "replace",
+            path: container.path.clone(),
+            value: image.to_string(),
+        }
+    }
+}
+
+/// Returns every container of a Pod or a workload's Pod template.
+pub(crate) fn containers(kind: &str, object: &serde_json::Value) -> Vec<Container> {
+    let path = match kind {
+        "Pod" => "/spec",
+        "CronJob" => "/spec/jobTemplate/spec/template/spec",
+        _ => "/spec/template/spec",
+    };
+
+    let spec = object.pointer(path).unwrap_or(&serde_json::Value::Null);
+
+    ["initContainers", "containers", "ephemeralContainers"]
+        .iter()
+        .filter_map(|containers| Some((containers, spec[containers].as_array()?)))
+        .flat_map(|(containers, array)| {
+            array
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, container)| {
+                    Some(Container {
+                        path: format!("{path}/{containers}/{index}/image"),
+                        image: container["image"].as_str()?.to_string(),
+                    })
+                })
+        })
+        .collect()
+}
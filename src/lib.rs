@@ -1,23 +1,65 @@
 #![warn(clippy::pedantic)]
 
+mod attestation;
+
+mod audit;
+
+mod break_glass;
+
+mod cache;
+
+pub mod check;
+
 pub mod configuration;
 
+mod delete;
+
+mod enforcement;
+
+pub mod fixture;
+
 mod http;
 
+mod kubernetes;
+
 mod logic;
 
+mod metadata;
+
+mod metrics;
+
+mod notification;
+
 pub mod oci;
 
+mod pattern;
+
+mod principal;
+
+mod push;
+
+mod quarantine;
+
+mod registry_event;
+
+pub mod reload;
+
+pub mod secret;
+
 pub mod server;
 
 mod route;
 
 pub mod shutdown;
 
+mod signature;
+
 mod snyk;
 
 mod state;
 
+mod tls;
+
 /// Error returned by most functions.
 ///
 /// For performance reasons, boxing is avoided in any hot path.
@@ -4,6 +4,23 @@ pub enum AdmitError {
     HighVulnerability,
     MediumVulnerability,
     LowVulnerability,
+    NotSigned,
+    InvalidSignature,
+    ProvenanceNotFound,
+    ProvenanceRejected,
+    SbomNotFound,
+    MissingLabel(String),
+    BaseImageNotAllowed(String),
+    TooOld,
+    RootUser,
+    PortNotAllowed(String),
+    TagNotAllowed(String),
+    ImmutableTag(String),
+    MediaTypeNotAllowed(String),
+    ForeignLayer,
+    SizeExceeded(u64),
+    DeleteDenied,
+    Quarantined(u64),
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
@@ -15,9 +32,32 @@ enum ProjectCriticality {
     None = 0,
 }
 
+/// Maximum number of vulnerabilities of each severity admitted.
+///
+/// Vulnerabilities of a severity accepted by the criticality of the project are not limited.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Thresholds {
+    pub(crate) critical: u32,
+    pub(crate) high: u32,
+    pub(crate) medium: u32,
+    pub(crate) low: u32,
+}
+
+impl From<&crate::configuration::Vulnerability> for Thresholds {
+    fn from(configuration: &crate::configuration::Vulnerability) -> Thresholds {
+        Thresholds {
+            critical: configuration.critical,
+            high: configuration.high,
+            medium: configuration.medium,
+            low: configuration.low,
+        }
+    }
+}
+
 /// Checks if the project is admitted.
 pub(crate) fn admitted(
     response: &crate::snyk::organization_projects_post::Response,
+    thresholds: &Thresholds,
 ) -> Result<(), AdmitError> {
     if let Some(project) = response.body.projects.first() {
         let criticality = &project.attributes.criticality;
@@ -34,13 +74,20 @@ pub(crate) fn admitted(
         };
 
         let issue_count = &project.issue_counts_by_severity;
-        if issue_count.critical > 0 && project_criticality < ProjectCriticality::Critical {
+        if issue_count.critical > thresholds.critical
+            && project_criticality < ProjectCriticality::Critical
+        {
             Err(AdmitError::CriticalVulnerability)
-        } else if issue_count.high > 0 && project_criticality < ProjectCriticality::High {
+        } else if issue_count.high > thresholds.high
+            && project_criticality < ProjectCriticality::High
+        {
             Err(AdmitError::HighVulnerability)
-        } else if issue_count.medium > 0 && project_criticality < ProjectCriticality::Medium {
+        } else if issue_count.medium > thresholds.medium
+            && project_criticality < ProjectCriticality::Medium
+        {
             Err(AdmitError::MediumVulnerability)
-        } else if issue_count.low > 0 && project_criticality < ProjectCriticality::Low {
+        } else if issue_count.low > thresholds.low && project_criticality < ProjectCriticality::Low
+        {
             Err(AdmitError::LowVulnerability)
         } else {
             Ok(())
@@ -68,6 +115,57 @@ impl std::fmt::Display for AdmitError {
             AdmitError::LowVulnerability => {
                 write!(f, "Image exceeded vulnerability threshold low")
             }
+            AdmitError::NotSigned => {
+                write!(f, "Image signature not found")
+            }
+            AdmitError::InvalidSignature => {
+                write!(f, "Image signature could not be verified")
+            }
+            AdmitError::ProvenanceNotFound => {
+                write!(f, "Image provenance attestation not found")
+            }
+            AdmitError::ProvenanceRejected => {
+                write!(f, "Image provenance attestation does not satisfy policy")
+            }
+            AdmitError::SbomNotFound => {
+                write!(f, "Image SBOM attestation not found")
+            }
+            AdmitError::MissingLabel(label) => {
+                write!(f, "Image label {label} not found")
+            }
+            AdmitError::BaseImageNotAllowed(base_image) => {
+                write!(f, "Image base image {base_image:?} not allowed")
+            }
+            AdmitError::TooOld => {
+                write!(f, "Image exceeded maximum age")
+            }
+            AdmitError::RootUser => {
+                write!(f, "Image runs as root")
+            }
+            AdmitError::PortNotAllowed(port) => {
+                write!(f, "Image exposed port {port} not allowed")
+            }
+            AdmitError::TagNotAllowed(tag) => {
+                write!(f, "Image tag {tag} not allowed")
+            }
+            AdmitError::ImmutableTag(tag) => {
+                write!(f, "Image tag {tag} is immutable")
+            }
+            AdmitError::MediaTypeNotAllowed(media_type) => {
+                write!(f, "Image media type {media_type} not allowed")
+            }
+            AdmitError::ForeignLayer => {
+                write!(f, "Image foreign layers not allowed")
+            }
+            AdmitError::SizeExceeded(max_size) => {
+                write!(f, "Image exceeded maximum size of {max_size} bytes")
+            }
+            AdmitError::DeleteDenied => {
+                write!(f, "Image deletion not allowed")
+            }
+            AdmitError::Quarantined(_) => {
+                write!(f, "Image quarantined until scan completes")
+            }
         }
     }
 }
@@ -0,0 +1,194 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    logic::AdmitError,
+    oci::{self, manifest::ImageConfiguration},
+};
+
+/// Annotation, or label, of the image the image was built from.
+const BASE_NAME: &str = "org.opencontainers.image.base.name";
+
+/// Admits images by the metadata of their manifest and configuration.
+#[derive(Clone)]
+pub(crate) struct Policy {
+    required_labels: Vec<String>,
+    base_images: Vec<String>,
+    max_age: Option<Duration>,
+    deny_root: bool,
+    allowed_ports: Option<Vec<String>>,
+}
+
+impl Policy {
+    /// Creates a new `Policy` instance.
+    pub(crate) fn new(configuration: crate::configuration::Metadata) -> Policy {
+        Policy {
+            required_labels: configuration.required_labels,
+            base_images: configuration.base_images,
+            max_age: configuration
+                .max_age_days
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            deny_root: configuration.deny_root,
+            allowed_ports: configuration
+                .allowed_ports
+                .map(|ports| ports.iter().map(|port| normalize_port(port)).collect()),
+        }
+    }
+
+    /// Evaluates the metadata of the manifest `reference`.
+    ///
+    /// The platform manifests of an image index are evaluated individually, inheriting the
+    /// annotations of the image index.
+    pub(crate) async fn evaluate(
+        &self,
+        client: &crate::http::Client,
+        upstream: &oci::Upstream,
+        name: &str,
+        reference: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<Result<(), AdmitError>> {
+        let Some(manifest) = upstream
+            .manifest(client, name, reference, authorization)
+            .await?
+        else {
+            return Err(format!("Manifest {name}:{reference} not found").into());
+        };
+
+        let mut manifests = Vec::new();
+
+        if oci::manifest::is_index(manifest.media_type()) {
+            // attestation manifests are attached to an image index as unknown platforms.
+            for descriptor in manifest.manifests().iter().filter(|descriptor| {
+                descriptor
+                    .platform
+                    .as_ref()
+                    .is_none_or(|platform| platform.os != "unknown")
+            }) {
+                if let Some(platform_manifest) = upstream
+                    .manifest(client, name, &descriptor.digest, authorization)
+                    .await?
+                {
+                    manifests.push(platform_manifest);
+                }
+            }
+        } else {
+            manifests.push(manifest.clone());
+        }
+
+        for platform_manifest in &manifests {
+            let Some(config) = platform_manifest.config() else {
+                continue;
+            };
+
+            let configuration = upstream
+                .blob(client, name, &config.digest, authorization)
+                .await?;
+            let configuration = serde_json::from_slice::<ImageConfiguration>(&configuration)?;
+
+            let annotations = manifest
+                .annotations()
+                .iter()
+                .chain(platform_manifest.annotations())
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+
+            if let Err(error) = self.admitted(&annotations, &configuration, SystemTime::now()) {
+                return Ok(Err(error));
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Checks if the manifest annotations and image configuration satisfy the policy.
+    fn admitted(
+        &self,
+        annotations: &BTreeMap<String, String>,
+        configuration: &ImageConfiguration,
+        now: SystemTime,
+    ) -> Result<(), AdmitError> {
+        let container = configuration.config.clone().unwrap_or_default();
+
+        let label = |key: &str| {
+            container
+                .labels
+                .get(key)
+                .or_else(|| annotations.get(key))
+                .filter(|value| !value.is_empty())
+        };
+
+        if let Some(missing) = self.required_labels.iter().find(|key| label(key).is_none()) {
+            return Err(AdmitError::MissingLabel(missing.clone()));
+        }
+
+        if !self.base_images.is_empty() {
+            let base_name = label(BASE_NAME).cloned().unwrap_or_default();
+
+            if !self
+                .base_images
+                .iter()
+                .any(|base_image| is_base_image(&base_name, base_image))
+            {
+                return Err(AdmitError::BaseImageNotAllowed(base_name));
+            }
+        }
+
+        if let Some(max_age) = self.max_age {
+            let created = configuration
+                .created
+                .as_deref()
+                .and_then(|created| humantime::parse_rfc3339_weak(created).ok());
+
+            match created {
+                Some(created) if now.duration_since(created).unwrap_or_default() <= max_age => {}
+                _ => return Err(AdmitError::TooOld),
+            }
+        }
+
+        if self.deny_root && is_root(container.user.as_deref().unwrap_or_default()) {
+            return Err(AdmitError::RootUser);
+        }
+
+        if let Some(allowed_ports) = &self.allowed_ports {
+            if let Some(port) = container
+                .exposed_ports
+                .keys()
+                .find(|port| !allowed_ports.contains(&normalize_port(port)))
+            {
+                return Err(AdmitError::PortNotAllowed(port.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks if the base image name is of the allowed repository, at any tag or digest.
+fn is_base_image(base_name: &str, base_image: &str) -> bool {
+    match base_name.strip_prefix(base_image) {
+        Some(rest) => rest.is_empty() || rest.starts_with(':') || rest.starts_with('@'),
+        None => false,
+    }
+}
+
+/// Checks if the user, in any of the `user[:group]` forms, is root.
+///
+/// Images without a user run as root.
+fn is_root(user: &str) -> bool {
+    let user = user.split(':').next().unwrap_or_default();
+
+    user.is_empty() || user == "root" || user == "0"
+}
+
+/// Normalizes a port to the `port/protocol` form, defaulting to TCP.
+fn normalize_port(port: &str) -> String {
+    let port = port.to_lowercase();
+
+    if port.contains('/') {
+        port
+    } else {
+        format!("{port}/tcp")
+    }
+}
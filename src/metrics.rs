@@ -0,0 +1,46 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{Arc, Mutex},
+};
+
+/// Counters exposed in the Prometheus text format.
+#[derive(Clone, Default)]
+pub(crate) struct Metrics {
+    counters: Arc<Mutex<BTreeMap<(&'static str, String), u64>>>,
+}
+
+impl Metrics {
+    /// Increments the counter `name` with the `labels`.
+    pub(crate) fn increment(&self, name: &'static str, labels: &[(&'static str, &str)]) {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{key}={value:?}"))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .entry((name, labels))
+            .or_default() += 1;
+    }
+
+    /// Renders the counters in the Prometheus text format.
+    pub(crate) fn render(&self) -> String {
+        let mut rendered = String::new();
+        let mut previous = None;
+
+        for ((name, labels), value) in self.counters.lock().unwrap().iter() {
+            if previous != Some(name) {
+                let _ = writeln!(rendered, "# TYPE {name} counter");
+                previous = Some(name);
+            }
+
+            let _ = writeln!(rendered, "{name}{{{labels}}} {value}");
+        }
+
+        rendered
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    configuration::NotificationEvent,
+    logic::{self, AdmitError},
+    pattern::{self, Glob},
+    secret::Secret,
+    snyk,
+};
+
+/// Sends notifications of admission and push events to webhooks.
+#[derive(Clone)]
+pub(crate) struct Notifier {
+    targets: Arc<Vec<Target>>,
+    sent: Arc<Mutex<HashMap<String, Instant>>>,
+    scan_interval: Duration,
+    scan_timeout: Duration,
+}
+
+struct Target {
+    url: Secret,
+    repositories: Vec<Glob>,
+    events: Vec<NotificationEvent>,
+    template: Option<String>,
+    deduplication_window: Duration,
+    retries: u32,
+}
+
+/// An event notified to the webhooks subscribed to it.
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct Event {
+    #[serde(rename = "event")]
+    pub(crate) kind: NotificationEvent,
+    pub(crate) repository: String,
+    pub(crate) reference: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) digest: Option<String>,
+    pub(crate) message: String,
+}
+
+impl Notifier {
+    /// Creates a new `Notifier` instance.
+    ///
+    /// # Errors
+    ///
+    /// If any of the repository patterns is malformed, an error is returned.
+    pub(crate) fn new(
+        configuration: crate::configuration::Notification,
+    ) -> crate::Result<Notifier> {
+        Ok(Notifier {
+            targets: Arc::new(
+                configuration
+                    .targets
+                    .into_iter()
+                    .map(|target| {
+                        Ok(Target {
+                            repositories: pattern::globs(&target.repositories)?,
+                            url: target.url,
+                            events: target.events,
+                            template: target.template,
+                            deduplication_window: Duration::from_secs(target.deduplication_window),
+                            retries: target.retries,
+                        })
+                    })
+                    .collect::<crate::Result<_>>()?,
+            ),
+            sent: Arc::new(Mutex::new(HashMap::new())),
+            scan_interval: Duration::from_secs(configuration.scan_interval),
+            scan_timeout: Duration::from_secs(configuration.scan_timeout),
+        })
+    }
+
+    /// Shares the notifications sent by the `previous` instance, so they stay deduplicated across
+    /// reloads.
+    pub(crate) fn carry_over(&mut self, previous: &Notifier) {
+        self.sent = previous.sent.clone();
+    }
+
+    /// Checks if any webhook is subscribed to the event of the repository `name`.
+    pub(crate) fn is_subscribed(&self, name: &str, event: NotificationEvent) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.is_subscribed(name, event))
+    }
+
+    /// Notifies the webhooks subscribed to the event, in the background.
+    ///
+    /// Events already notified to a webhook within its deduplication window are not notified
+    /// again.
+    pub(crate) fn notify(&self, client: &crate::http::Client, event: &Event) {
+        let now = Instant::now();
+        let mut sent = self.sent.lock().unwrap();
+
+        sent.retain(|_, at| {
+            self.targets
+                .iter()
+                .any(|target| now.duration_since(*at) < target.deduplication_window)
+        });
+
+        for (index, target) in self.targets.iter().enumerate() {
+            if !target.is_subscribed(&event.repository, event.kind) {
+                continue;
+            }
+
+            let key = format!(
+                "{index}/{:?}/{}/{}/{}",
+                event.kind, event.repository, event.reference, event.message
+            );
+
+            if sent
+                .get(&key)
+                .is_some_and(|at| now.duration_since(*at) < target.deduplication_window)
+            {
+                continue;
+            }
+
+            sent.insert(key, now);
+
+            let body = match target.payload(event) {
+                Ok(body) => body,
+                Err(error) => {
+                    tracing::error!(?error, target = index, "Failed to render notification");
+                    continue;
+                }
+            };
+
+            tokio::spawn(deliver(
+                client.clone(),
+                target.url.clone(),
+                body,
+                target.retries,
+            ));
+        }
+    }
+
+    /// Watches the scan of a pushed image in the background, notifying the webhooks if it finds
+    /// critical vulnerabilities.
+    pub(crate) fn watch_scan(
+        &self,
+        client: &crate::http::Client,
+        snyk_api: &snyk::Api,
+        scanner_name: String,
+        event: Event,
+    ) {
+        let notifier = self.clone();
+        let client = client.clone();
+        let snyk_api = snyk_api.clone();
+
+        tokio::spawn(async move {
+            let deadline = Instant::now() + notifier.scan_timeout;
+
+            while Instant::now() < deadline {
+                tokio::time::sleep(notifier.scan_interval).await;
+
+                let scan = match snyk_api
+                    .send_organization_projects_post(&client, scanner_name.clone())
+                    .await
+                {
+                    Ok(scan) => scan,
+                    Err(error) => {
+                        tracing::warn!(?error, "Failed to fetch scan results");
+                        continue;
+                    }
+                };
+
+                // critical vulnerabilities are notified regardless of the admission thresholds.
+                match logic::admitted(&scan, &logic::Thresholds::default()) {
+                    Err(AdmitError::NotMonitored) => continue,
+                    Err(error @ AdmitError::CriticalVulnerability) => notifier.notify(
+                        &client,
+                        &Event {
+                            kind: NotificationEvent::Critical,
+                            message: error.to_string(),
+                            ..event
+                        },
+                    ),
+                    _ => {}
+                }
+
+                return;
+            }
+        });
+    }
+}
+
+impl Target {
+    /// Checks if the webhook is subscribed to the event of the repository `name`.
+    ///
+    /// Webhooks without repository patterns or events are subscribed to every repository or
+    /// event.
+    fn is_subscribed(&self, name: &str, event: NotificationEvent) -> bool {
+        (self.repositories.is_empty() || self.repositories.iter().any(|glob| glob.is_match(name)))
+            && (self.events.is_empty() || self.events.contains(&event))
+    }
+
+    /// Renders the payload of the event.
+    ///
+    /// Templates reference the properties of the event as `{{property}}`, which are substituted
+    /// JSON escaped. Without a template, the event is sent with a `text` summary, as understood by
+    /// Slack and Microsoft Teams.
+    fn payload(&self, event: &Event) -> crate::Result<Vec<u8>> {
+        let text = format!("{}:{} {}", event.repository, event.reference, event.message);
+
+        let Some(template) = &self.template else {
+            let mut payload = serde_json::to_value(event)?;
+            payload["text"] = text.into();
+            return Ok(serde_json::to_vec(&payload)?);
+        };
+
+        let escape = |value: &str| {
+            let escaped = serde_json::to_string(value).unwrap_or_default();
+            escaped[1..escaped.len() - 1].to_string()
+        };
+
+        let event_name = serde_json::to_value(event.kind)?;
+
+        Ok(template
+            .replace(
+                "{{event}}",
+                &escape(event_name.as_str().unwrap_or_default()),
+            )
+            .replace("{{repository}}", &escape(&event.repository))
+            .replace("{{reference}}", &escape(&event.reference))
+            .replace(
+                "{{digest}}",
+                &escape(event.digest.as_deref().unwrap_or_default()),
+            )
+            .replace("{{message}}", &escape(&event.message))
+            .replace("{{text}}", &escape(&text))
+            .into_bytes())
+    }
+}
+
+/// Delivers the payload to the webhook, retrying with exponential backoff.
+///
+/// Webhook URLs commonly embed their token, so only their host is logged.
+async fn deliver(client: crate::http::Client, url: Secret, body: Vec<u8>, retries: u32) {
+    let mut backoff = Duration::from_secs(1);
+
+    let host = url
+        .expose()
+        .parse::<hyper::Uri>()
+        .ok()
+        .and_then(|uri| uri.host().map(ToString::to_string))
+        .unwrap_or_default();
+
+    for attempt in 0..=retries {
+        let request = hyper::Request::post(url.expose())
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(body.clone()));
+
+        let outcome = match request {
+            Ok(request) => client.request(request).await.map_err(crate::Error::from),
+            Err(error) => Err(error.into()),
+        };
+
+        match outcome {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => {
+                tracing::warn!(%host, attempt, status = %response.status(), "Notification rejected");
+            }
+            Err(error) => tracing::warn!(%host, attempt, ?error, "Notification failed"),
+        }
+
+        if attempt < retries {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    tracing::error!(%host, "Notification abandoned");
+}
@@ -0,0 +1,329 @@
+use sha2::Digest as _;
+
+use super::{
+    manifest::{ImageIndex, Manifest},
+    Upstream,
+};
+
+impl Upstream {
+    /// Fetches a manifest from the upstream, on behalf of the client's `authorization`.
+    ///
+    /// Returns `None` if the manifest does not exist.
+    pub(crate) async fn manifest(
+        &self,
+        client: &crate::http::Client,
+        name: &str,
+        reference: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<Option<Manifest>> {
+        let response = self
+            .get(
+                client,
+                &format!("/v2/{name}/manifests/{reference}"),
+                Some(&super::manifest::accept()),
+                authorization,
+            )
+            .await?;
+
+        if response.status() == hyper::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if response.status() != hyper::StatusCode::OK {
+            return Err(format!(
+                "Failed to fetch manifest {name}:{reference}, {}",
+                response.status()
+            )
+            .into());
+        }
+
+        let media_type = response
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let digest = format!("sha256:{:x}", sha2::Sha256::digest(&body));
+
+        if reference.contains(':') && reference != digest {
+            return Err(
+                format!("Manifest digest mismatch, expected {reference}, actual {digest}").into(),
+            );
+        }
+
+        Manifest::from_slice(media_type.as_deref(), &body).map(Some)
+    }
+
+    /// Fetches the digest of a manifest from the upstream, on behalf of the client's
+    /// `authorization`.
+    ///
+    /// Returns `None` if the manifest does not exist.
+    pub(crate) async fn manifest_digest(
+        &self,
+        client: &crate::http::Client,
+        name: &str,
+        reference: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<Option<String>> {
+        let response = self
+            .request(
+                hyper::Method::HEAD,
+                client,
+                &format!("/v2/{name}/manifests/{reference}"),
+                Some(&super::manifest::accept()),
+                authorization,
+            )
+            .await?;
+
+        if response.status() == hyper::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if response.status() != hyper::StatusCode::OK {
+            return Err(format!(
+                "Failed to fetch manifest digest {name}:{reference}, {}",
+                response.status()
+            )
+            .into());
+        }
+
+        let digest = response
+            .headers()
+            .get("docker-content-digest")
+            .ok_or_else(|| format!("Manifest {name}:{reference} has no digest"))?
+            .to_str()?;
+
+        Ok(Some(digest.to_string()))
+    }
+
+    /// Fetches a blob from the upstream, on behalf of the client's `authorization`.
+    ///
+    /// Blobs are verified against their digest, and redirects are followed.
+    pub(crate) async fn blob(
+        &self,
+        client: &crate::http::Client,
+        name: &str,
+        digest: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<hyper::body::Bytes> {
+        let mut response = self
+            .get(
+                client,
+                &format!("/v2/{name}/blobs/{digest}"),
+                None,
+                authorization,
+            )
+            .await?;
+
+        if response.status().is_redirection() {
+            if let Some(location) = response.headers().get(hyper::header::LOCATION) {
+                let location = location.to_str()?;
+
+                response = if location.starts_with('/') {
+                    self.get(client, location, None, authorization).await?
+                } else {
+                    // credentials are never sent to a third party.
+                    client.get(location.parse()?).await?
+                };
+            }
+        }
+
+        if response.status() != hyper::StatusCode::OK {
+            return Err(format!(
+                "Failed to fetch blob {name}@{digest}, {}",
+                response.status()
+            )
+            .into());
+        }
+
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let actual = format!("sha256:{:x}", sha2::Sha256::digest(&body));
+
+        if actual != digest {
+            return Err(format!("Blob digest mismatch, expected {digest}, actual {actual}").into());
+        }
+
+        Ok(body)
+    }
+
+    /// Fetches the referrers of a manifest from the upstream, on behalf of the client's
+    /// `authorization`.
+    ///
+    /// If the upstream does not support the referrers API, the referrers tag schema is used.
+    pub(crate) async fn referrers(
+        &self,
+        client: &crate::http::Client,
+        name: &str,
+        digest: &str,
+        artifact_type: Option<&str>,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<ImageIndex> {
+        let path = match artifact_type {
+            Some(artifact_type) => format!(
+                "/v2/{name}/referrers/{digest}?artifactType={}",
+                form_urlencoded::byte_serialize(artifact_type.as_bytes()).collect::<String>()
+            ),
+            None => format!("/v2/{name}/referrers/{digest}"),
+        };
+
+        let response = self
+            .get(
+                client,
+                &path,
+                Some(super::manifest::OCI_IMAGE_INDEX),
+                authorization,
+            )
+            .await?;
+
+        let mut index = match response.status() {
+            hyper::StatusCode::OK => {
+                let body = hyper::body::to_bytes(response.into_body()).await?;
+                serde_json::from_slice::<ImageIndex>(&body)?
+            }
+            hyper::StatusCode::NOT_FOUND => {
+                self.referrers_tag(client, name, digest, authorization)
+                    .await?
+            }
+            status => {
+                return Err(format!("Failed to fetch referrers {name}@{digest}, {status}").into())
+            }
+        };
+
+        // upstreams are not required to apply the filter.
+        if let Some(artifact_type) = artifact_type {
+            index.retain_artifact_type(artifact_type);
+        }
+
+        Ok(index)
+    }
+
+    /// Fetches the referrers of a manifest from the upstream through the referrers tag schema,
+    /// on behalf of the client's `authorization`.
+    ///
+    /// Returns an empty image index if the referrers tag does not exist.
+    pub(crate) async fn referrers_tag(
+        &self,
+        client: &crate::http::Client,
+        name: &str,
+        digest: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<ImageIndex> {
+        match self
+            .manifest(client, name, &referrers_tag(digest), authorization)
+            .await?
+        {
+            Some(Manifest::ImageIndex(index)) => Ok(index),
+            Some(manifest) => Err(format!(
+                "Referrers tag of {name}@{digest} is not an image index, {}",
+                manifest.media_type()
+            )
+            .into()),
+            None => Ok(ImageIndex::empty()),
+        }
+    }
+
+    /// Checks the client's `authorization` grants access to the content at `path` of the
+    /// upstream, with a `HEAD` request on its behalf.
+    ///
+    /// The credentials of the upstream are never used, as they would grant the gateway's access.
+    ///
+    /// Returns the response of the upstream, successful if access is granted.
+    pub(crate) async fn authorize(
+        &self,
+        client: &crate::http::Client,
+        path: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        self.request_as(
+            hyper::Method::HEAD,
+            client,
+            path,
+            Some(&super::manifest::accept()),
+            authorization,
+        )
+        .await
+    }
+
+    /// Sends a `GET` request to the upstream.
+    async fn get(
+        &self,
+        client: &crate::http::Client,
+        path_and_query: &str,
+        accept: Option<&str>,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        self.request(
+            hyper::Method::GET,
+            client,
+            path_and_query,
+            accept,
+            authorization,
+        )
+        .await
+    }
+
+    /// Sends a request without a body to the upstream, on behalf of the client's
+    /// `authorization`, or else of the credentials of the upstream, if any.
+    async fn request(
+        &self,
+        method: hyper::Method,
+        client: &crate::http::Client,
+        path_and_query: &str,
+        accept: Option<&str>,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        let credentials = match (authorization, &self.credentials) {
+            (None, Some(credentials)) => Some(credentials.authorization()?),
+            (_, _) => None,
+        };
+
+        self.request_as(
+            method,
+            client,
+            path_and_query,
+            accept,
+            authorization.or(credentials.as_ref()),
+        )
+        .await
+    }
+
+    /// Sends a request without a body to the upstream, on behalf of the client's
+    /// `authorization` only.
+    async fn request_as(
+        &self,
+        method: hyper::Method,
+        client: &crate::http::Client,
+        path_and_query: &str,
+        accept: Option<&str>,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        let mut request = hyper::Request::builder()
+            .method(method)
+            .uri(format!("{}{path_and_query}", self.base_address));
+
+        if let Some(accept) = accept {
+            request = request.header(hyper::header::ACCEPT, accept);
+        }
+
+        if let Some(authorization) = authorization {
+            request = request.header(hyper::header::AUTHORIZATION, authorization);
+        }
+
+        client
+            .request(request.body(hyper::Body::empty())?)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+/// Returns the tag the referrers of the manifest `digest` are stored under by the referrers tag
+/// schema.
+///
+/// <https://github.com/opencontainers/distribution-spec/blob/main/spec.md#referrers-tag-schema>
+fn referrers_tag(digest: &str) -> String {
+    let tag = digest.replace(':', "-");
+
+    // tags are limited to 128 characters.
+    tag.chars().take(128).collect()
+}
@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+
+/// Media type of an OCI image index.
+pub const OCI_IMAGE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+
+/// Media type of an OCI image manifest.
+pub const OCI_IMAGE_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Media type of an OCI image configuration.
+pub const OCI_IMAGE_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+
+/// Media type of the OCI empty descriptor, used by artifacts without a configuration.
+pub const OCI_EMPTY: &str = "application/vnd.oci.empty.v1+json";
+
+/// Media type of a Docker image manifest list.
+pub const DOCKER_MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// Media type of a Docker image manifest, schema version 2.
+pub const DOCKER_MANIFEST: &str = "application/vnd.docker.distribution.manifest.v2+json";
+
+/// Media type of a Docker image configuration.
+pub const DOCKER_CONTAINER_IMAGE: &str = "application/vnd.docker.container.image.v1+json";
+
+/// Media type of a Docker foreign layer, which is not pushed to the registry.
+pub const DOCKER_FOREIGN_LAYER: &str = "application/vnd.docker.image.rootfs.foreign.diff.tar.gzip";
+
+/// Media types of manifests, in order of preference.
+pub const MANIFEST_MEDIA_TYPES: [&str; 4] = [
+    OCI_IMAGE_INDEX,
+    OCI_IMAGE_MANIFEST,
+    DOCKER_MANIFEST_LIST,
+    DOCKER_MANIFEST,
+];
+
+/// A manifest of any of the supported media types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Manifest {
+    ImageIndex(ImageIndex),
+    ImageManifest(ImageManifest),
+    DockerManifestList(DockerManifestList),
+    DockerManifest(DockerManifest),
+}
+
+/// <https://github.com/opencontainers/image-spec/blob/main/image-index.md>
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    pub manifests: Vec<Descriptor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Descriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// <https://github.com/opencontainers/image-spec/blob/main/manifest.md>
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageManifest {
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Descriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// <https://docs.docker.com/registry/spec/manifest-v2-2/#manifest-list>
+///
+/// A manifest list is structurally an image index without annotations.
+pub type DockerManifestList = ImageIndex;
+
+/// <https://docs.docker.com/registry/spec/manifest-v2-2/#image-manifest>
+///
+/// A schema version 2 manifest is structurally an image manifest without annotations.
+pub type DockerManifest = ImageManifest;
+
+/// <https://github.com/opencontainers/image-spec/blob/main/descriptor.md>
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// <https://github.com/opencontainers/image-spec/blob/main/image-index.md#image-index-property-descriptions>
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    #[serde(rename = "os.version", skip_serializing_if = "Option::is_none")]
+    pub os_version: Option<String>,
+    #[serde(rename = "os.features", default, skip_serializing_if = "Vec::is_empty")]
+    pub os_features: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+}
+
+/// <https://github.com/opencontainers/image-spec/blob/main/config.md>
+///
+/// Also describes the Docker image configuration, which the OCI image configuration is based on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ImageConfiguration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub architecture: String,
+    #[serde(default)]
+    pub os: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<ContainerConfiguration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rootfs: Option<RootFs>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<History>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerConfiguration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub exposed_ports: BTreeMap<String, serde_json::Value>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub env: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub entrypoint: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub cmd: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub volumes: BTreeMap<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_signal: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RootFs {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub diff_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct History {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub empty_layer: bool,
+}
+
+impl Manifest {
+    /// Parses a manifest of the given media type.
+    ///
+    /// If the media type is not known, the `mediaType` property of the manifest is used instead.
+    ///
+    /// # Errors
+    ///
+    /// If the manifest is malformed or of an unsupported media type, an error is returned.
+    pub fn from_slice(media_type: Option<&str>, slice: &[u8]) -> crate::Result<Manifest> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct MediaType {
+            media_type: Option<String>,
+            manifests: Option<serde_json::Value>,
+        }
+
+        let media_type =
+            if let Some(media_type) = media_type.filter(|media_type| is_manifest(media_type)) {
+                media_type.to_string()
+            } else {
+                let probe = serde_json::from_slice::<MediaType>(slice)?;
+
+                match (probe.media_type, probe.manifests) {
+                    (Some(media_type), _) => media_type,
+                    // media type is optional in OCI manifests, but only an index has manifests.
+                    (None, Some(_)) => OCI_IMAGE_INDEX.to_string(),
+                    (None, None) => OCI_IMAGE_MANIFEST.to_string(),
+                }
+            };
+
+        match media_type.as_str() {
+            OCI_IMAGE_INDEX => Ok(Manifest::ImageIndex(serde_json::from_slice(slice)?)),
+            OCI_IMAGE_MANIFEST => Ok(Manifest::ImageManifest(serde_json::from_slice(slice)?)),
+            DOCKER_MANIFEST_LIST => {
+                Ok(Manifest::DockerManifestList(serde_json::from_slice(slice)?))
+            }
+            DOCKER_MANIFEST => Ok(Manifest::DockerManifest(serde_json::from_slice(slice)?)),
+            media_type => Err(format!("Unsupported manifest media type {media_type}").into()),
+        }
+    }
+
+    /// Returns the media type of the manifest.
+    #[must_use]
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Manifest::ImageIndex(_) => OCI_IMAGE_INDEX,
+            Manifest::ImageManifest(_) => OCI_IMAGE_MANIFEST,
+            Manifest::DockerManifestList(_) => DOCKER_MANIFEST_LIST,
+            Manifest::DockerManifest(_) => DOCKER_MANIFEST,
+        }
+    }
+
+    /// Returns the manifests referenced by an image index or manifest list.
+    #[must_use]
+    pub fn manifests(&self) -> &[Descriptor] {
+        match self {
+            Manifest::ImageIndex(index) | Manifest::DockerManifestList(index) => &index.manifests,
+            Manifest::ImageManifest(_) | Manifest::DockerManifest(_) => &[],
+        }
+    }
+
+    /// Returns the configuration referenced by an image manifest.
+    #[must_use]
+    pub fn config(&self) -> Option<&Descriptor> {
+        match self {
+            Manifest::ImageManifest(manifest) | Manifest::DockerManifest(manifest) => {
+                Some(&manifest.config)
+            }
+            Manifest::ImageIndex(_) | Manifest::DockerManifestList(_) => None,
+        }
+    }
+
+    /// Returns the layers referenced by an image manifest.
+    #[must_use]
+    pub fn layers(&self) -> &[Descriptor] {
+        match self {
+            Manifest::ImageManifest(manifest) | Manifest::DockerManifest(manifest) => {
+                &manifest.layers
+            }
+            Manifest::ImageIndex(_) | Manifest::DockerManifestList(_) => &[],
+        }
+    }
+
+    /// Returns the annotations of the manifest.
+    #[must_use]
+    pub fn annotations(&self) -> &BTreeMap<String, String> {
+        match self {
+            Manifest::ImageIndex(index) | Manifest::DockerManifestList(index) => &index.annotations,
+            Manifest::ImageManifest(manifest) | Manifest::DockerManifest(manifest) => {
+                &manifest.annotations
+            }
+        }
+    }
+}
+
+impl ImageIndex {
+    /// Returns an image index without manifests, as returned by the referrers API for manifests
+    /// without referrers.
+    #[must_use]
+    pub fn empty() -> ImageIndex {
+        ImageIndex {
+            schema_version: 2,
+            media_type: Some(OCI_IMAGE_INDEX.to_string()),
+            artifact_type: None,
+            manifests: Vec::new(),
+            subject: None,
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    /// Retains the manifests of the artifact type only.
+    pub fn retain_artifact_type(&mut self, artifact_type: &str) {
+        self.manifests
+            .retain(|manifest| manifest.artifact_type.as_deref() == Some(artifact_type));
+    }
+}
+
+/// Checks if the media type is an image index or a manifest list.
+#[must_use]
+pub fn is_index(media_type: &str) -> bool {
+    media_type == OCI_IMAGE_INDEX || media_type == DOCKER_MANIFEST_LIST
+}
+
+/// Checks if the media type is any of the supported manifest media types.
+#[must_use]
+pub fn is_manifest(media_type: &str) -> bool {
+    MANIFEST_MEDIA_TYPES.contains(&media_type)
+}
+
+/// Returns the `Accept` header value requesting all supported manifest media types.
+#[must_use]
+pub fn accept() -> String {
+    MANIFEST_MEDIA_TYPES.join(", ")
+}
+
+/// Parses the media types of `Accept` header values, ignoring parameters.
+pub fn accepted<'a>(values: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    values
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(|value| value.split(';').next())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Checks if the media type is acceptable to the client.
+///
+/// Clients not sending an `Accept` header accept any media type.
+#[must_use]
+pub fn is_acceptable(accepted: &[String], media_type: &str) -> bool {
+    accepted.is_empty()
+        || accepted
+            .iter()
+            .any(|accepted| accepted == media_type || accepted == "*/*")
+}
+
+/// Deserializes `null` as the default value.
+///
+/// Docker image configurations commonly contain `null` for empty collections.
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + serde::Deserialize<'de>,
+{
+    use serde::Deserialize as _;
+
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
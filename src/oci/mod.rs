@@ -0,0 +1,477 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use zeroize::Zeroizing;
+
+use crate::{break_glass, principal, secret};
+
+mod fetch;
+
+pub mod manifest;
+
+#[derive(Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Response {
+    pub errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ResponseError {
+    pub code: String,
+    pub message: String,
+    pub details: Option<()>,
+}
+
+/// Maps manifest digests back to the tag they were pulled through.
+///
+/// Clients pulling a multi platform tag resolve the tag to an image index, and then pull each
+/// platform manifest by digest.
+#[derive(Clone, Default)]
+pub(crate) struct Lineage {
+    entries: Arc<Mutex<HashMap<String, Parent>>>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Parent {
+    /// Tag the manifest was pulled through.
+    pub(crate) tag: String,
+    /// Digest of the image index referencing the manifest, if any.
+    pub(crate) index: Option<String>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Proxy {
+    upstream: Upstream,
+    upstreams: Vec<Upstream>,
+    mirror: bool,
+    name_endpoint: regex::Regex,
+}
+
+#[derive(Clone)]
+pub(crate) struct Upstream {
+    prefix: Option<String>,
+    registry: Option<String>,
+    base_address: String,
+    credentials: Option<Credentials>,
+}
+
+/// Credentials the gateway pulls from an upstream with, on behalf of clients sending none.
+#[derive(Clone, Debug)]
+pub(crate) struct Credentials {
+    username: String,
+    password: secret::Source,
+}
+
+pub(crate) struct ProxyRequest {
+    upstream: Upstream,
+    path_and_query: String,
+    request: hyper::Request<hyper::Body>,
+}
+
+pub(crate) struct ProxyResponse {
+    upstream: Upstream,
+    response: hyper::Response<hyper::Body>,
+}
+
+#[allow(clippy::struct_field_names)]
+#[derive(Clone)]
+pub(crate) struct Regex {
+    pub(crate) name_blob_digest: regex::Regex,
+    pub(crate) name_manifest_reference: regex::Regex,
+    pub(crate) name_referrers_digest: regex::Regex,
+}
+
+impl Proxy {
+    /// Creates a new `Proxy` instance.
+    ///
+    /// Requests for repositories not served by any of the `upstreams` are sent to `upstream`.
+    ///
+    /// In mirror mode, the `ns` query parameter sent by containerd selects the upstream by
+    /// registry, and official image names are normalized for Docker Hub.
+    pub(crate) fn new(
+        upstream: Upstream,
+        upstreams: impl IntoIterator<Item = Upstream>,
+        mirror: bool,
+    ) -> Proxy {
+        let mut upstreams = upstreams.into_iter().collect::<Vec<_>>();
+
+        // the most specific prefix is matched first.
+        upstreams.sort_by_key(|upstream| {
+            std::cmp::Reverse(upstream.prefix.as_ref().map_or(0, String::len))
+        });
+
+        Proxy {
+            upstream,
+            upstreams,
+            mirror,
+            name_endpoint: regex::Regex::new(
+                r"^/v2/(?P<name>.+)/(?P<endpoint>manifests/[^/]+|blobs/uploads/.*|blobs/[^/]+|tags/list|referrers/[^/]+)$",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Returns the name on the gateway of the `repository` of the `registry`.
+    ///
+    /// Returns `None` if no upstream serves the registry under a name of the gateway.
+    pub(crate) fn name(&self, registry: &str, repository: &str) -> Option<String> {
+        // upstreams without a prefix are only reachable through the `ns` query parameter, which
+        // pulls by name do not send.
+        let upstream = self
+            .upstreams
+            .iter()
+            .filter(|upstream| upstream.prefix.is_some())
+            .chain(std::iter::once(&self.upstream))
+            .find(|upstream| upstream.registry.as_deref() == Some(registry))?;
+
+        let repository = if upstream.is_docker_hub() && !repository.contains('/') {
+            Cow::Owned(format!("library/{repository}"))
+        } else {
+            Cow::Borrowed(repository)
+        };
+
+        Some(match &upstream.prefix {
+            Some(prefix) => format!("{prefix}/{repository}"),
+            None => repository.into_owned(),
+        })
+    }
+
+    /// Resolves the upstream serving the `repository` of the `registry`.
+    ///
+    /// Returns `None` if no upstream serves the registry.
+    pub(crate) fn resolve_registry<'a>(
+        &self,
+        registry: &str,
+        repository: &'a str,
+    ) -> Option<(&Upstream, Cow<'a, str>)> {
+        let upstream = self
+            .upstreams
+            .iter()
+            .chain(std::iter::once(&self.upstream))
+            .find(|upstream| upstream.registry.as_deref() == Some(registry))?;
+
+        if upstream.is_docker_hub() && !repository.contains('/') {
+            Some((upstream, Cow::Owned(format!("library/{repository}"))))
+        } else {
+            Some((upstream, Cow::Borrowed(repository)))
+        }
+    }
+
+    /// Checks if an upstream serves the registry requested through the `ns` query parameter.
+    ///
+    /// The `namespace` is only honored in mirror mode, any request is served otherwise.
+    pub(crate) fn is_served(&self, namespace: Option<&str>) -> bool {
+        match namespace.filter(|_| self.mirror) {
+            Some(namespace) => self
+                .upstreams
+                .iter()
+                .chain(std::iter::once(&self.upstream))
+                .any(|upstream| upstream.registry.as_deref() == Some(namespace)),
+            None => true,
+        }
+    }
+
+    /// Resolves the upstream serving the repository `name`.
+    ///
+    /// `namespace` is the registry requested through the `ns` query parameter, if any, which must
+    /// be served as checked by [`Proxy::is_served`].
+    ///
+    /// Returns the upstream and the name of the repository on the upstream.
+    pub(crate) fn resolve<'a>(
+        &self,
+        name: &'a str,
+        namespace: Option<&str>,
+    ) -> (&Upstream, Cow<'a, str>) {
+        let (upstream, name) = match namespace.filter(|_| self.mirror) {
+            Some(namespace) => (
+                self.upstreams
+                    .iter()
+                    .chain(std::iter::once(&self.upstream))
+                    .find(|upstream| upstream.registry.as_deref() == Some(namespace))
+                    .unwrap_or(&self.upstream),
+                name,
+            ),
+            None => self
+                .upstreams
+                .iter()
+                .find_map(|upstream| {
+                    upstream
+                        .strip_prefix(name)
+                        .map(|upstream_name| (upstream, upstream_name))
+                })
+                .unwrap_or((&self.upstream, name)),
+        };
+
+        if self.mirror && upstream.is_docker_hub() && !name.contains('/') {
+            (upstream, Cow::Owned(format!("library/{name}")))
+        } else {
+            (upstream, Cow::Borrowed(name))
+        }
+    }
+
+    /// Creates a new `ProxyRequest` instance.
+    pub(crate) fn request(&self, request: impl Into<hyper::Request<hyper::Body>>) -> ProxyRequest {
+        let request = request.into();
+
+        let namespace = namespace(request.uri());
+        let path = request.uri().path();
+
+        let (upstream, path) = match self.name_endpoint.captures(path) {
+            Some(captures) => {
+                let (upstream, name) = self.resolve(
+                    captures.name("name").unwrap().as_str(),
+                    namespace.as_deref(),
+                );
+
+                (upstream, format!("/v2/{name}/{}", &captures["endpoint"]))
+            }
+            None => (self.resolve("", namespace.as_deref()).0, path.to_string()),
+        };
+
+        let query = request.uri().query().map(|query| {
+            query
+                .split('&')
+                .filter(|parameter| !(self.mirror && parameter.starts_with("ns=")))
+                .collect::<Vec<_>>()
+                .join("&")
+        });
+
+        let path_and_query = match query {
+            Some(query) if !query.is_empty() => format!("{path}?{query}"),
+            _ => path,
+        };
+
+        ProxyRequest {
+            upstream: upstream.clone(),
+            path_and_query,
+            request,
+        }
+    }
+
+    /// Sends a request.
+    ///
+    /// A convenience method for proxying a request to the backend.
+    pub(crate) async fn send(
+        &self,
+        client: &crate::http::Client,
+        request: impl Into<hyper::Request<hyper::Body>>,
+    ) -> crate::Result<hyper::Response<hyper::Body>> {
+        let proxy_request = self.request(request.into());
+
+        let upstream = proxy_request.upstream.clone();
+
+        let proxy_response = upstream.response(client.request(proxy_request.try_into()?).await?);
+
+        proxy_response.try_into()
+    }
+}
+
+impl Upstream {
+    /// Creates a new `Upstream` instance.
+    ///
+    /// Repositories starting with `prefix` are served by `base_address`, with the prefix removed.
+    ///
+    /// In mirror mode, requests for the `registry` namespace are served by `base_address`.
+    ///
+    /// Pulls of clients sending no credentials are sent with the `credentials`, if any.
+    pub(crate) fn new(
+        prefix: Option<String>,
+        registry: Option<String>,
+        base_address: impl Into<String>,
+        credentials: Option<Credentials>,
+    ) -> Upstream {
+        Upstream {
+            prefix: prefix.map(|prefix| prefix.trim_matches('/').to_string()),
+            registry,
+            base_address: base_address.into(),
+            credentials,
+        }
+    }
+
+    /// Returns the base address of the upstream.
+    pub(crate) fn base_address(&self) -> &str {
+        &self.base_address
+    }
+
+    /// Creates a new `ProxyResponse` instance.
+    pub(crate) fn response(
+        &self,
+        response: impl Into<hyper::Response<hyper::Body>>,
+    ) -> ProxyResponse {
+        ProxyResponse {
+            upstream: self.clone(),
+            response: response.into(),
+        }
+    }
+
+    /// Removes the prefix from the repository `name`.
+    ///
+    /// Returns `None` if the repository is not served by this upstream.
+    fn strip_prefix<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let prefix = self.prefix.as_ref()?;
+
+        name.strip_prefix(prefix.as_str())?.strip_prefix('/')
+    }
+
+    /// Checks if the upstream is Docker Hub, which serves official images under `library/`.
+    fn is_docker_hub(&self) -> bool {
+        self.registry.as_deref() == Some("docker.io")
+    }
+
+    /// Rewrites a location returned by the upstream to a location on the gateway.
+    fn location(&self, location: &str) -> String {
+        let location = location
+            .strip_prefix(&self.base_address)
+            .unwrap_or(location);
+
+        match (&self.prefix, location.strip_prefix("/v2/")) {
+            (Some(prefix), Some(location)) => format!("/v2/{prefix}/{location}"),
+            (_, _) => location.to_string(),
+        }
+    }
+}
+
+impl Credentials {
+    /// Creates a new `Credentials` instance.
+    pub(crate) fn new(username: String, password: secret::Source) -> Credentials {
+        Credentials { username, password }
+    }
+
+    /// Returns the `Authorization` header value of the credentials, with the current password.
+    fn authorization(&self) -> crate::Result<hyper::header::HeaderValue> {
+        use base64::Engine as _;
+
+        let password = self.password.get();
+        let credentials = Zeroizing::new(format!("{}:{}", self.username, password.expose()));
+
+        secret::Secret::new(
+            base64::engine::general_purpose::STANDARD.encode(credentials.as_bytes()),
+        )
+        .authorization("Basic")
+    }
+}
+
+impl Lineage {
+    const MAX_ENTRIES: usize = 100_000;
+
+    /// Returns the parent of the manifest `digest` in the `repository`.
+    pub(crate) fn get(&self, repository: &str, digest: &str) -> Option<Parent> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&format!("{repository}@{digest}"))
+            .cloned()
+    }
+
+    /// Records the parent of the manifest `digest` in the `repository`.
+    pub(crate) fn insert(&self, repository: &str, digest: &str, parent: Parent) {
+        let mut entries = self.entries.lock().unwrap();
+
+        // lineage is rebuilt on the next pull of the tag.
+        if entries.len() >= Self::MAX_ENTRIES {
+            entries.clear();
+        }
+
+        entries.insert(format!("{repository}@{digest}"), parent);
+    }
+}
+
+/// Returns the registry requested through the `ns` query parameter, if any.
+pub(crate) fn namespace(uri: &hyper::Uri) -> Option<String> {
+    query(uri, "ns")
+}
+
+/// Returns the decoded value of the query parameter `key`, if any.
+pub(crate) fn query(uri: &hyper::Uri, key: &str) -> Option<String> {
+    form_urlencoded::parse(uri.query()?.as_bytes())
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+impl TryFrom<ProxyRequest> for hyper::Request<hyper::Body> {
+    type Error = crate::Error;
+
+    fn try_from(this: ProxyRequest) -> Result<Self, Self::Error> {
+        let request = hyper::Request::builder()
+            .method(this.request.method())
+            .uri(format!(
+                "{}{}",
+                this.upstream.base_address, this.path_and_query
+            ));
+
+        let request = this
+            .request
+            .headers()
+            .iter()
+            .filter(|(header_name, _)| {
+                header_name != &hyper::header::HOST
+                    && *header_name != principal::CLIENT_CERTIFICATE
+                    && *header_name != break_glass::HEADER
+            })
+            .fold(request, |request, (header_name, header_value)| {
+                request.header(header_name, header_value)
+            });
+
+        // only pulls are sent on behalf of the gateway, pushes and deletes require credentials.
+        let is_pull = matches!(
+            *this.request.method(),
+            hyper::Method::GET | hyper::Method::HEAD
+        );
+
+        let request = match &this.upstream.credentials {
+            Some(credentials)
+                if is_pull
+                    && !this
+                        .request
+                        .headers()
+                        .contains_key(hyper::header::AUTHORIZATION) =>
+            {
+                request.header(hyper::header::AUTHORIZATION, credentials.authorization()?)
+            }
+            _ => request,
+        };
+
+        request.body(this.request.into_body()).map_err(Into::into)
+    }
+}
+
+impl TryFrom<ProxyResponse> for hyper::Response<hyper::Body> {
+    type Error = crate::Error;
+
+    fn try_from(this: ProxyResponse) -> Result<Self, Self::Error> {
+        let response = hyper::Response::builder().status(this.response.status());
+
+        let response = this.response.headers().iter().fold(
+            response,
+            |response, (header_name, header_value)| match (header_name, header_value.to_str()) {
+                (&hyper::header::LOCATION, Ok(location)) => {
+                    response.header(header_name, this.upstream.location(location))
+                }
+                (_, _) => response.header(header_name, header_value),
+            },
+        );
+
+        response.body(this.response.into_body()).map_err(Into::into)
+    }
+}
+
+impl Default for Regex {
+    fn default() -> Self {
+        Self {
+            name_blob_digest: regex::Regex::new(
+                r"^/v2/(?P<name>.*)/blobs/(?P<digest>[a-z0-9]+(?:[+._-][a-z0-9]+)*:[a-zA-Z0-9=_-]+)$",
+            )
+            .unwrap(),
+            name_manifest_reference: regex::Regex::new(
+                r"/v2/(?P<name>.*)/manifests/(?P<reference>.*)",
+            )
+            .unwrap(),
+            name_referrers_digest: regex::Regex::new(
+                r"^/v2/(?P<name>.*)/referrers/(?P<digest>[a-z0-9]+(?:[+._-][a-z0-9]+)*:[a-zA-Z0-9=_-]+)$",
+            )
+            .unwrap(),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+/// A glob pattern matching repository names.
+///
+/// `*` matches within a path segment, and `**` matches across path segments.
+#[derive(Clone)]
+pub(crate) struct Glob(regex::Regex);
+
+impl Glob {
+    /// Creates a new `Glob` instance.
+    ///
+    /// # Errors
+    ///
+    /// If the pattern can not be compiled, an error is returned.
+    pub(crate) fn new(pattern: &str) -> crate::Result<Glob> {
+        let mut expression = String::from("^");
+        let mut characters = pattern.chars().peekable();
+
+        while let Some(character) = characters.next() {
+            match character {
+                '*' if characters.peek() == Some(&'*') => {
+                    characters.next();
+                    expression.push_str(".*");
+                }
+                '*' => expression.push_str("[^/]*"),
+                '?' => expression.push_str("[^/]"),
+                character => expression.push_str(&regex::escape(&character.to_string())),
+            }
+        }
+
+        expression.push('$');
+
+        Ok(Glob(regex::Regex::new(&expression)?))
+    }
+
+    /// Checks if the name matches the pattern.
+    pub(crate) fn is_match(&self, name: &str) -> bool {
+        self.0.is_match(name)
+    }
+}
+
+/// Compiles the glob patterns.
+pub(crate) fn globs(patterns: &[String]) -> crate::Result<Vec<Glob>> {
+    patterns.iter().map(|pattern| Glob::new(pattern)).collect()
+}
+
+/// Compiles the regular expressions, anchored to match whole values.
+pub(crate) fn anchored(patterns: &[String]) -> crate::Result<Vec<regex::Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| regex::Regex::new(&format!("^(?:{pattern})$")).map_err(Into::into))
+        .collect()
+}
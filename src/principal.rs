@@ -0,0 +1,52 @@
+use base64::Engine as _;
+
+/// Header carrying the subject of the client certificate verified by the gateway.
+///
+/// The header is set by the gateway itself, any value sent by the client is discarded.
+pub(crate) const CLIENT_CERTIFICATE: &str = "x-gateway-client-certificate";
+
+/// Returns the principal the client authenticates as, if any.
+///
+/// The principal is the subject of a client certificate verified by the gateway, or else the
+/// username of basic credentials, or the subject of a bearer token. The credentials are not
+/// verified by the gateway, so the principal may only be relied upon for requests the upstream
+/// authorizes with the same credentials.
+pub(crate) fn principal(headers: &hyper::HeaderMap) -> Option<String> {
+    if let Some(subject) = headers
+        .get(CLIENT_CERTIFICATE)
+        .and_then(|value| value.to_str().ok())
+    {
+        return Some(subject.to_string());
+    }
+
+    let authorization = headers.get(hyper::header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = authorization.split_once(' ')?;
+
+    if scheme.eq_ignore_ascii_case("basic") {
+        let credentials = base64::engine::general_purpose::STANDARD
+            .decode(credentials.trim())
+            .ok()?;
+        let credentials = String::from_utf8(credentials).ok()?;
+
+        credentials
+            .split_once(':')
+            .map(|(username, _)| username.to_string())
+    } else if scheme.eq_ignore_ascii_case("bearer") {
+        #[derive(serde::Deserialize)]
+        struct Claims {
+            sub: String,
+        }
+
+        let payload = credentials.trim().split('.').nth(1)?;
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(payload.trim_end_matches('='))
+            .ok()?;
+
+        serde_json::from_slice::<Claims>(&payload)
+            .ok()
+            .map(|claims| claims.sub)
+    } else {
+        None
+    }
+    .filter(|principal| !principal.is_empty())
+}
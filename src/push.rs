@@ -0,0 +1,135 @@
+use crate::{
+    logic::AdmitError,
+    oci::manifest::{Manifest, DOCKER_FOREIGN_LAYER},
+    pattern::{self, Glob},
+};
+
+/// Media type prefix of OCI non-distributable layers.
+const OCI_NONDISTRIBUTABLE_LAYER: &str = "application/vnd.oci.image.layer.nondistributable.";
+
+/// Admits pushes by the manifest being pushed, before it reaches the upstream.
+#[derive(Clone)]
+pub(crate) struct Policy {
+    immutability: Vec<Immutability>,
+    tag_patterns: Vec<regex::Regex>,
+    media_types: Vec<String>,
+    deny_foreign_layers: bool,
+    max_size: Option<u64>,
+}
+
+impl Policy {
+    /// Creates a new `Policy` instance.
+    ///
+    /// # Errors
+    ///
+    /// If any of the tag patterns is not a valid regular expression, an error is returned.
+    pub(crate) fn new(configuration: crate::configuration::Push) -> crate::Result<Policy> {
+        Ok(Policy {
+            immutability: configuration
+                .immutability
+                .iter()
+                .map(Immutability::new)
+                .collect::<crate::Result<_>>()?,
+            tag_patterns: pattern::anchored(&configuration.tag_patterns)?,
+            media_types: configuration.media_types,
+            deny_foreign_layers: configuration.deny_foreign_layers,
+            max_size: configuration.max_size,
+        })
+    }
+
+    /// Checks if the manifest pushed as `reference` satisfies the policy.
+    pub(crate) fn admitted(&self, reference: &str, manifest: &Manifest) -> Result<(), AdmitError> {
+        let is_tag = !reference.contains(':');
+
+        if is_tag
+            && !self.tag_patterns.is_empty()
+            && !self
+                .tag_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(reference))
+        {
+            return Err(AdmitError::TagNotAllowed(reference.to_string()));
+        }
+
+        let media_type = manifest.media_type();
+
+        if !self.media_types.is_empty() && !self.media_types.iter().any(|m| m == media_type) {
+            return Err(AdmitError::MediaTypeNotAllowed(media_type.to_string()));
+        }
+
+        if self.deny_foreign_layers
+            && manifest.layers().iter().any(|layer| {
+                layer.media_type == DOCKER_FOREIGN_LAYER
+                    || layer.media_type.starts_with(OCI_NONDISTRIBUTABLE_LAYER)
+                    || !layer.urls.is_empty()
+            })
+        {
+            return Err(AdmitError::ForeignLayer);
+        }
+
+        if let Some(max_size) = self.max_size {
+            // the platform manifests of an image index are checked when they are pushed.
+            let size = manifest
+                .config()
+                .into_iter()
+                .chain(manifest.layers())
+                .map(|descriptor| descriptor.size)
+                .sum::<u64>();
+
+            if size > max_size {
+                return Err(AdmitError::SizeExceeded(max_size));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the tag of the repository `name` may not be overwritten by the `principal` once
+    /// pushed.
+    pub(crate) fn is_immutable(
+        &self,
+        name: &str,
+        reference: &str,
+        principal: Option<&str>,
+    ) -> bool {
+        !reference.contains(':')
+            && self
+                .immutability
+                .iter()
+                .any(|immutability| immutability.applies(name, reference, principal))
+    }
+}
+
+/// A rule making tags of repositories immutable.
+#[derive(Clone)]
+struct Immutability {
+    repositories: Vec<Glob>,
+    tags: Vec<regex::Regex>,
+    principals: Vec<String>,
+}
+
+impl Immutability {
+    fn new(configuration: &crate::configuration::PushImmutability) -> crate::Result<Immutability> {
+        Ok(Immutability {
+            repositories: pattern::globs(&configuration.repositories)?,
+            tags: pattern::anchored(&configuration.tags)?,
+            principals: configuration.principals.clone(),
+        })
+    }
+
+    /// Checks if the rule applies to the tag, unless the principal may overwrite tags.
+    ///
+    /// Rules without repository or tag patterns apply to every repository or tag.
+    fn applies(&self, name: &str, tag: &str, principal: Option<&str>) -> bool {
+        let repository = self.repositories.is_empty()
+            || self.repositories.iter().any(|glob| glob.is_match(name));
+        let tag = self.tags.is_empty() || self.tags.iter().any(|pattern| pattern.is_match(tag));
+        let privileged = principal.is_some_and(|principal| {
+            self.principals
+                .iter()
+                .any(|privileged| privileged == principal)
+        });
+
+        repository && tag && !privileged
+    }
+}
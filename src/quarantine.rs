@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Holds newly pushed manifests until the scanner reports on them.
+#[derive(Clone)]
+pub(crate) struct Quarantine {
+    wait: Duration,
+    poll_interval: Duration,
+    expiry: Duration,
+    pending: Arc<Mutex<HashMap<String, Pending>>>,
+}
+
+/// A manifest held in quarantine.
+#[derive(Clone, serde::Serialize)]
+pub(crate) struct Pending {
+    pub(crate) repository: String,
+    pub(crate) digest: String,
+    pub(crate) tag: Option<String>,
+    #[serde(serialize_with = "rfc3339")]
+    pub(crate) since: SystemTime,
+}
+
+impl Quarantine {
+    /// Creates a new `Quarantine` instance.
+    pub(crate) fn new(configuration: &crate::configuration::Quarantine) -> Quarantine {
+        Quarantine {
+            wait: Duration::from_secs(configuration.wait),
+            poll_interval: Duration::from_secs(configuration.poll_interval),
+            expiry: Duration::from_secs(configuration.expiry),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Shares the manifests held by the `previous` instance, so they stay held across reloads.
+    pub(crate) fn carry_over(&mut self, previous: &Quarantine) {
+        self.pending = previous.pending.clone();
+    }
+
+    /// Duration pulls of pending manifests are blocked for, waiting for the scanner.
+    pub(crate) fn wait(&self) -> Duration {
+        self.wait
+    }
+
+    /// Interval the scanner is polled at while a pull is blocked.
+    pub(crate) fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Holds the manifest `digest` of the `repository`, pushed as `tag` if any.
+    pub(crate) fn hold(&self, repository: &str, digest: &str, tag: Option<&str>) {
+        self.pending.lock().unwrap().insert(
+            key(repository, digest),
+            Pending {
+                repository: repository.to_string(),
+                digest: digest.to_string(),
+                tag: tag.map(ToString::to_string),
+                since: SystemTime::now(),
+            },
+        );
+    }
+
+    /// Checks if any of the manifest `digests` of the `repository` is held.
+    ///
+    /// Manifests are released once held for longer than the expiry, whether the scanner has
+    /// reported on them or not.
+    pub(crate) fn is_pending(&self, repository: &str, digests: &[String]) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+
+        pending.retain(|_, pending| pending.since.elapsed().unwrap_or_default() < self.expiry);
+
+        digests
+            .iter()
+            .any(|digest| pending.contains_key(&key(repository, digest)))
+    }
+
+    /// Releases the manifest `digests` of the `repository`.
+    pub(crate) fn release(&self, repository: &str, digests: &[String]) {
+        let mut pending = self.pending.lock().unwrap();
+
+        for digest in digests {
+            if let Some(pending) = pending.remove(&key(repository, digest)) {
+                tracing::info!(repository, digest, since = ?pending.since, "Released from quarantine");
+            }
+        }
+    }
+
+    /// Returns the manifests held, oldest first.
+    pub(crate) fn pending(&self) -> Vec<Pending> {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|pending| pending.since.elapsed().unwrap_or_default() < self.expiry)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        pending.sort_by_key(|pending| pending.since);
+
+        pending
+    }
+}
+
+fn key(repository: &str, digest: &str) -> String {
+    format!("{repository}@{digest}")
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn rfc3339<S: serde::Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&humantime::format_rfc3339_seconds(*time))
+}
@@ -0,0 +1,182 @@
+use hmac::Mac as _;
+
+use crate::secret;
+
+/// Receives the notifications of pushes made directly to the upstream, bypassing the gateway.
+///
+/// Docker Distribution, Harbor and Amazon ECR notifications, delivered by `EventBridge`, are understood.
+#[derive(Clone)]
+pub(crate) struct Receiver {
+    secret: secret::Source,
+}
+
+/// A manifest pushed to the upstream.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Pushed {
+    pub(crate) repository: String,
+    pub(crate) tag: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Payload {
+    Distribution {
+        events: Vec<DistributionEvent>,
+    },
+    Harbor {
+        #[serde(rename = "type")]
+        kind: String,
+        event_data: HarborEventData,
+    },
+    Ecr {
+        #[serde(rename = "detail-type")]
+        detail_type: String,
+        detail: EcrDetail,
+    },
+}
+
+#[derive(serde::Deserialize)]
+struct DistributionEvent {
+    action: String,
+    target: DistributionTarget,
+}
+
+#[derive(serde::Deserialize)]
+struct DistributionTarget {
+    repository: String,
+    tag: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct HarborEventData {
+    repository: HarborRepository,
+    resources: Vec<HarborResource>,
+}
+
+#[derive(serde::Deserialize)]
+struct HarborRepository {
+    repo_full_name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct HarborResource {
+    tag: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct EcrDetail {
+    #[serde(rename = "action-type")]
+    action_type: String,
+    result: String,
+    #[serde(rename = "repository-name")]
+    repository_name: String,
+    #[serde(rename = "image-tag")]
+    image_tag: Option<String>,
+}
+
+impl Receiver {
+    /// Creates a new `Receiver` instance.
+    ///
+    /// # Errors
+    ///
+    /// If the secret file cannot be read, an error is returned.
+    pub(crate) fn new(
+        configuration: &crate::configuration::RegistryEvents,
+    ) -> crate::Result<Receiver> {
+        Ok(Receiver {
+            secret: secret::Source::new(
+                configuration.secret.as_ref(),
+                configuration.secret_file.as_deref(),
+            )?,
+        })
+    }
+
+    /// Checks the notification was sent by a registry knowing the secret.
+    ///
+    /// The secret is either sent as the `Authorization` header, optionally as a bearer token, or
+    /// used to sign the body as a `X-Hub-Signature-256` HMAC-SHA256 signature.
+    pub(crate) fn is_authentic(&self, headers: &hyper::HeaderMap, body: &[u8]) -> bool {
+        let secret = self.secret.get();
+
+        if let Some(authorization) = headers
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+        {
+            let token = authorization
+                .strip_prefix("Bearer ")
+                .unwrap_or(authorization);
+
+            return secret::constant_time_eq(token.as_bytes(), secret.expose().as_bytes());
+        }
+
+        let Some(signature) = headers
+            .get("x-hub-signature-256")
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+
+        let Ok(mut mac) = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.expose().as_bytes())
+        else {
+            return false;
+        };
+
+        mac.update(body);
+
+        let expected = format!("sha256={:x}", mac.finalize().into_bytes());
+
+        secret::constant_time_eq(signature.as_bytes(), expected.as_bytes())
+    }
+}
+
+/// Parses the tagged manifests pushed from a registry notification.
+///
+/// Blob pushes, pushes by digest and other events are ignored, as the scanner imports images by
+/// tag.
+///
+/// # Errors
+///
+/// If the notification is not understood, an error is returned.
+pub(crate) fn parse(body: &[u8]) -> crate::Result<Vec<Pushed>> {
+    let pushed = match serde_json::from_slice(body)? {
+        Payload::Distribution { events } => events
+            .into_iter()
+            .filter(|event| event.action == "push")
+            .filter_map(|event| {
+                Some(Pushed {
+                    tag: event.target.tag?,
+                    repository: event.target.repository,
+                })
+            })
+            .collect(),
+        Payload::Harbor { kind, event_data } if kind == "PUSH_ARTIFACT" => event_data
+            .resources
+            .into_iter()
+            .filter_map(|resource| {
+                Some(Pushed {
+                    repository: event_data.repository.repo_full_name.clone(),
+                    tag: resource.tag?,
+                })
+            })
+            .collect(),
+        Payload::Ecr {
+            detail_type,
+            detail,
+        } if detail_type == "ECR Image Action"
+            && detail.action_type == "PUSH"
+            && detail.result == "SUCCESS" =>
+        {
+            detail
+                .image_tag
+                .map(|tag| Pushed {
+                    repository: detail.repository_name,
+                    tag,
+                })
+                .into_iter()
+                .collect()
+        }
+        Payload::Harbor { .. } | Payload::Ecr { .. } => Vec::new(),
+    };
+
+    Ok(pushed)
+}
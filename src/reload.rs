@@ -0,0 +1,70 @@
+use std::{path::PathBuf, time::SystemTime};
+
+use tokio::sync::mpsc::Sender;
+
+use crate::configuration;
+
+/// Interval the configuration files are polled for changes at.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Watches for configuration changes, sending the reloaded configuration on SIGHUP or when a
+/// configuration file changes.
+///
+/// Configurations which fail to load are logged and not sent.
+///
+/// # Panics
+///
+/// Panics if the signal handler cannot be installed.
+pub async fn watch(sender: Sender<configuration::Configuration>) {
+    #[cfg(unix)]
+    let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("Failed to install SIGHUP handler");
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut modified = modification_times();
+
+    loop {
+        #[cfg(unix)]
+        let hangup = sighup.recv();
+
+        #[cfg(not(unix))]
+        let hangup = std::future::pending::<Option<()>>();
+
+        let trigger = tokio::select! {
+            _ = hangup => "SIGHUP",
+            _ = interval.tick() => {
+                let current = modification_times();
+
+                if current == modified {
+                    continue;
+                }
+
+                modified = current;
+                "file change"
+            }
+        };
+
+        tracing::info!(trigger, "Reloading configuration");
+
+        match configuration::load(&[]) {
+            Ok(configuration) => {
+                if sender.send(configuration).await.is_err() {
+                    return;
+                }
+            }
+            Err(error) => tracing::error!(?error, "Failed to load configuration"),
+        }
+    }
+}
+
+/// Returns the modification times of the configuration files.
+fn modification_times() -> Vec<Option<SystemTime>> {
+    configuration::files()
+        .into_iter()
+        .map(|path: PathBuf| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
@@ -1,6 +1,388 @@
 use axum::{extract::Path, http::status::StatusCode, Extension};
+use sha2::Digest as _;
 
-use crate::{logic, oci, state::State};
+use crate::{
+    audit, break_glass,
+    configuration::{AdmissionIndex, EnforcementMode, NotificationEvent},
+    delete, kubernetes, logic, notification, oci, principal, snyk,
+    state::State,
+};
+
+/// Header listing the filters applied to a referrers response.
+const OCI_FILTERS_APPLIED: &str = "oci-filters-applied";
+
+/// Outcome of the admission policy for a manifest.
+pub(crate) struct Admission {
+    pub(crate) admitted: Result<(), logic::AdmitError>,
+    /// Scan results of the manifest the policy was evaluated on.
+    pub(crate) scan: snyk::organization_projects_post::Response,
+}
+
+/// POST /break-glass
+///
+/// Issues a break-glass token, letting pulls of a manifest bypass admission until it expires.
+///
+/// Requires the admin token of the break-glass configuration.
+pub(crate) async fn break_glass_post(
+    state: Extension<State>,
+    headers: axum::http::HeaderMap,
+    axum::Json(request): axum::Json<break_glass::IssueRequest>,
+) -> Result<axum::Json<break_glass::Issued>, StatusCode> {
+    let break_glass = state.break_glass.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+
+    if !break_glass.is_admin(&headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let (token, claims) = break_glass
+        .issue(
+            request.repository,
+            request.digest,
+            request.ttl.map(std::time::Duration::from_secs),
+            request.reason,
+        )
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    tracing::warn!(
+        repository = %claims.repository,
+        digest = %claims.digest,
+        expires = claims.expires,
+        reason = %claims.reason,
+        "Break-glass token issued"
+    );
+    audit::record(
+        "break_glass_issue",
+        &claims.repository,
+        &claims.digest,
+        principal::principal(&headers).as_deref(),
+        "issued",
+    );
+
+    if let Some(notifier) = &state.notifier {
+        notifier.notify(
+            &state.http_client,
+            &notification::Event {
+                kind: NotificationEvent::BreakGlass,
+                repository: claims.repository.clone(),
+                reference: claims.digest.clone(),
+                digest: Some(claims.digest.clone()),
+                message: format!("Break-glass token issued: {}", claims.reason),
+            },
+        );
+    }
+
+    Ok(axum::Json(break_glass::Issued { token, claims }))
+}
+
+/// POST /admission/mutate
+///
+/// Kubernetes mutating admission webhook, rewriting the images of Pods and workloads to be pulled
+/// through the gateway, pinned to the digest which was admitted.
+pub(crate) async fn admission_mutate_post(
+    state: Extension<State>,
+    axum::Json(review): axum::Json<kubernetes::AdmissionReview>,
+) -> Result<axum::Json<kubernetes::AdmissionReview>, StatusCode> {
+    let webhook = state
+        .kubernetes_webhook
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let request = review.request.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let mut messages = Vec::new();
+    let mut patch = Vec::new();
+
+    for container in kubernetes::containers(&request.kind.kind, &request.object) {
+        let Some(mut image) =
+            webhook.rewrite(&state.oci_proxy, kubernetes::Image::parse(&container.image))
+        else {
+            continue;
+        };
+
+        let (upstream, upstream_name) = state.oci_proxy.resolve(&image.repository, None);
+
+        let Ok(digest) = admission_image_digest(&state, upstream, &upstream_name, &image).await
+        else {
+            messages.push(format!(
+                "{}: Image digest could not be resolved",
+                container.image
+            ));
+            continue;
+        };
+
+        image.digest = digest;
+
+        if let Err(error) = admission_image_admitted(&state, upstream, &upstream_name, &image)
+            .await?
+            .admitted
+        {
+            admission_image_denied(&state, &image, &error);
+            messages.push(format!("{}: {error}", container.image));
+            continue;
+        }
+
+        if image.digest.is_some() {
+            image.tag = None;
+        }
+
+        if image.to_string() != container.image {
+            patch.push(kubernetes::Patch::image(&container, &image));
+        }
+    }
+
+    if !messages.is_empty() {
+        return Ok(axum::Json(kubernetes::AdmissionReview::response(
+            request.uid,
+            &messages,
+        )));
+    }
+
+    kubernetes::AdmissionReview::patched(request.uid, &patch)
+        .map(axum::Json)
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// POST /admission/validate
+///
+/// Kubernetes validating admission webhook, denying Pods and workloads referencing images which
+/// would be denied when pulled through the gateway.
+pub(crate) async fn admission_validate_post(
+    state: Extension<State>,
+    axum::Json(review): axum::Json<kubernetes::AdmissionReview>,
+) -> Result<axum::Json<kubernetes::AdmissionReview>, StatusCode> {
+    let webhook = state
+        .kubernetes_webhook
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let request = review.request.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let mut images = kubernetes::containers(&request.kind.kind, &request.object)
+        .into_iter()
+        .map(|container| container.image)
+        .collect::<Vec<_>>();
+    images.sort();
+    images.dedup();
+
+    let mut messages = Vec::new();
+
+    for reference in images {
+        let mut image = kubernetes::Image::parse(&reference);
+
+        let (upstream, upstream_name) = match webhook.upstream(&state.oci_proxy, &image) {
+            Some(Ok((upstream, upstream_name))) => (upstream, upstream_name.into_owned()),
+            Some(Err(error)) => {
+                messages.push(format!("{reference}: {error}"));
+                continue;
+            }
+            None => continue,
+        };
+
+        let Ok(digest) = admission_image_digest(&state, upstream, &upstream_name, &image).await
+        else {
+            messages.push(format!("{reference}: Image digest could not be resolved"));
+            continue;
+        };
+
+        image.digest = digest;
+
+        if let Err(error) = admission_image_admitted(&state, upstream, &upstream_name, &image)
+            .await?
+            .admitted
+        {
+            admission_image_denied(&state, &image, &error);
+            messages.push(format!("{reference}: {error}"));
+        }
+    }
+
+    Ok(axum::Json(kubernetes::AdmissionReview::response(
+        request.uid,
+        &messages,
+    )))
+}
+
+/// Resolves the digest of an image referenced by a workload, on the `upstream` serving it.
+///
+/// Returns `None` if the image does not exist.
+pub(crate) async fn admission_image_digest(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    image: &kubernetes::Image,
+) -> Result<Option<String>, StatusCode> {
+    if let Some(digest) = &image.digest {
+        return Ok(Some(digest.clone()));
+    }
+
+    upstream
+        .manifest_digest(
+            &state.http_client,
+            upstream_name,
+            image.tag.as_deref().unwrap_or_default(),
+            None,
+        )
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::BAD_GATEWAY
+        })
+}
+
+/// Evaluates the admission policy for an image referenced by a workload, on the `upstream`
+/// serving it once its digest is resolved.
+pub(crate) async fn admission_image_admitted(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    image: &kubernetes::Image,
+) -> Result<Admission, StatusCode> {
+    let parent = admission_image_parent(state, upstream, upstream_name, image);
+    let scanner_reference = admission_image_scanner_reference(image, parent.as_ref());
+
+    let digests = image
+        .digest
+        .clone()
+        .into_iter()
+        .chain(parent.and_then(|parent| parent.index))
+        .collect::<Vec<_>>();
+
+    v2_manifest_admitted(
+        state,
+        upstream,
+        upstream_name,
+        &scanner_reference,
+        &digests,
+        None,
+    )
+    .await
+}
+
+/// Returns the parent of an image referenced by a workload by digest, if it was pulled through a
+/// tag.
+fn admission_image_parent(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    image: &kubernetes::Image,
+) -> Option<oci::Parent> {
+    let repository = format!("{}/{upstream_name}", upstream.base_address());
+
+    image
+        .digest
+        .as_ref()
+        .and_then(|digest| state.oci_lineage.get(&repository, digest))
+}
+
+/// Returns the reference the scanner knows an image referenced by a workload by.
+///
+/// Scanners know images by tag, so images pinned by digest are looked up through their parent,
+/// or else by their digest.
+fn admission_image_scanner_reference(
+    image: &kubernetes::Image,
+    parent: Option<&oci::Parent>,
+) -> String {
+    image
+        .tag
+        .clone()
+        .or_else(|| parent.map(|parent| parent.tag.clone()))
+        .or_else(|| image.digest.clone())
+        .unwrap_or_default()
+}
+
+/// Notifies the denial of an image referenced by a workload.
+fn admission_image_denied(
+    state: &Extension<State>,
+    image: &kubernetes::Image,
+    error: &logic::AdmitError,
+) {
+    v2_manifest_denied(
+        state,
+        image.repository.clone(),
+        image
+            .tag
+            .clone()
+            .or_else(|| image.digest.clone())
+            .unwrap_or_default(),
+        image.digest.clone(),
+        error,
+    );
+}
+
+/// POST /delete/restore
+///
+/// Restores soft deleted content, so it can be pulled again.
+///
+/// Restorations are authorized as the soft deletions, with the upstream granting the client
+/// access to the repository, selected through the `ns` query parameter in mirror mode.
+pub(crate) async fn delete_restore_post(
+    state: Extension<State>,
+    uri: hyper::Uri,
+    headers: axum::http::HeaderMap,
+    axum::Json(request): axum::Json<delete::RestoreRequest>,
+) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    let policy = state.delete_policy.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let namespace = oci::namespace(&uri);
+    let (upstream, upstream_name) = state
+        .oci_proxy
+        .resolve(&request.repository, namespace.as_deref());
+    let repository = format!("{}/{upstream_name}", upstream.base_address());
+    let principal = principal::principal(&headers);
+
+    if let delete::Decision::Deny(error) = policy.decide(&request.repository, principal.as_deref())
+    {
+        audit::record(
+            "restore",
+            &repository,
+            &request.reference,
+            principal.as_deref(),
+            "denied",
+        );
+
+        return denied(&error);
+    }
+
+    if let Some(response) = v2_content_unauthorized(
+        &state,
+        upstream,
+        &format!("/v2/{upstream_name}/tags/list"),
+        &headers,
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+
+    let restored = policy
+        .restore(&repository, &request.reference, principal.as_deref())
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if !restored {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    audit::record(
+        "restore",
+        &repository,
+        &request.reference,
+        principal.as_deref(),
+        "restored",
+    );
+
+    hyper::Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(hyper::Body::empty())
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
 
 /// GET /health/liveness
 ///
@@ -22,6 +404,97 @@ pub(crate) async fn health_readiness_get() -> StatusCode {
     StatusCode::OK
 }
 
+/// GET /metrics
+///
+/// Returns the metrics of the gateway in the Prometheus text format.
+///
+/// Requires the admin token of the break-glass configuration.
+#[allow(clippy::unused_async)]
+pub(crate) async fn metrics_get(
+    state: Extension<State>,
+    headers: axum::http::HeaderMap,
+) -> Result<String, StatusCode> {
+    if !is_admin(&state, &headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(state.metrics.render())
+}
+
+/// GET /quarantine
+///
+/// Returns the manifests held in quarantine until the scanner reports on them.
+///
+/// Requires the admin token of the break-glass configuration.
+#[allow(clippy::unused_async)]
+pub(crate) async fn quarantine_get(
+    state: Extension<State>,
+    headers: axum::http::HeaderMap,
+) -> Result<axum::Json<Vec<crate::quarantine::Pending>>, StatusCode> {
+    if !is_admin(&state, &headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    state
+        .quarantine
+        .as_ref()
+        .map(|quarantine| axum::Json(quarantine.pending()))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Checks the request is authorized by the admin token, if break-glass is configured.
+fn is_admin(state: &Extension<State>, headers: &hyper::HeaderMap) -> bool {
+    state
+        .break_glass
+        .as_ref()
+        .is_some_and(|break_glass| break_glass.is_admin(headers))
+}
+
+/// POST /registry/events
+///
+/// Receives the notifications of pushes made directly to the upstream, importing each pushed
+/// manifest into the scanner.
+///
+/// Returns 502 if any import fails, so the registry retries the notification.
+pub(crate) async fn registry_events_post(
+    state: Extension<State>,
+    headers: axum::http::HeaderMap,
+    body: axum::body::Bytes,
+) -> StatusCode {
+    let Some(receiver) = &state.registry_event_receiver else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    if !receiver.is_authentic(&headers, &body) {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let pushed = match crate::registry_event::parse(&body) {
+        Ok(pushed) => pushed,
+        Err(error) => {
+            tracing::warn!(?error, "Registry notification not understood");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+
+    let mut status = StatusCode::ACCEPTED;
+
+    for pushed in pushed {
+        let name = format!("{}:{}", pushed.repository, pushed.tag);
+
+        if let Err(error) = state
+            .snyk_api
+            .send_organization_integration_import_post(&state.http_client, name.clone())
+            .await
+        {
+            tracing::error!(?error, %name, "Failed to import pushed image");
+            status = StatusCode::BAD_GATEWAY;
+        }
+    }
+
+    status
+}
+
 /// Router for /v2/* nested routes
 ///
 /// This router is used by the OCI distribution specification proxy.
@@ -32,12 +505,39 @@ pub(crate) async fn v2_routes(
     state: Extension<State>,
     request: axum::http::Request<axum::body::Body>,
 ) -> Result<hyper::Response<hyper::Body>, StatusCode> {
-    let uri = request.uri().to_string();
+    let path = request.uri().path().to_string();
 
-    let name_manifest_reference = state.oci_regex.name_manifest_reference.captures(&uri);
+    // pulls must never be served by an upstream other than the one of the requested registry.
+    if !state
+        .oci_proxy
+        .is_served(oci::namespace(request.uri()).as_deref())
+    {
+        return oci_error(
+            StatusCode::NOT_FOUND,
+            "NAME_UNKNOWN",
+            "repository name not known to registry",
+        );
+    }
+
+    let name_blob_digest = state.oci_regex.name_blob_digest.captures(&path);
+    let name_manifest_reference = state.oci_regex.name_manifest_reference.captures(&path);
+    let name_referrers_digest = state.oci_regex.name_referrers_digest.captures(&path);
 
-    match (request.method(), name_manifest_reference) {
-        (&(axum::http::Method::GET | axum::http::Method::HEAD), Some(captures)) => {
+    match (
+        request.method(),
+        name_blob_digest,
+        name_manifest_reference,
+        name_referrers_digest,
+    ) {
+        (&(axum::http::Method::GET | axum::http::Method::HEAD), Some(captures), _, _) => {
+            v2_name_blob_digest_get_head(
+                &state,
+                Path((captures["name"].to_string(), captures["digest"].to_string())),
+                request,
+            )
+            .await
+        }
+        (&(axum::http::Method::GET | axum::http::Method::HEAD), _, Some(captures), _) => {
             v2_name_manifest_reference_get_head(
                 &state,
                 Path((
@@ -48,7 +548,7 @@ pub(crate) async fn v2_routes(
             )
             .await
         }
-        (&axum::http::Method::PUT, Some(captures)) => {
+        (&axum::http::Method::PUT, _, Some(captures), _) => {
             v2_name_manifest_reference_put(
                 &state,
                 Path((
@@ -59,6 +559,33 @@ pub(crate) async fn v2_routes(
             )
             .await
         }
+        (&axum::http::Method::DELETE, Some(captures), _, _) => {
+            v2_name_content_delete(
+                &state,
+                Path((captures["name"].to_string(), captures["digest"].to_string())),
+                request,
+            )
+            .await
+        }
+        (&axum::http::Method::DELETE, _, Some(captures), _) => {
+            v2_name_content_delete(
+                &state,
+                Path((
+                    captures["name"].to_string(),
+                    captures["reference"].to_string(),
+                )),
+                request,
+            )
+            .await
+        }
+        (&axum::http::Method::GET, _, _, Some(captures)) => {
+            v2_name_referrers_digest_get(
+                &state,
+                Path((captures["name"].to_string(), captures["digest"].to_string())),
+                request,
+            )
+            .await
+        }
         _ => v2_proxy(&state, request).await,
     }
 }
@@ -66,62 +593,494 @@ pub(crate) async fn v2_routes(
 /// GET|HEAD /v2/:name/manifests/:reference
 ///
 /// This endpoint is used by the OCI distribution specification proxy.
+///
+/// The manifest is fetched before admission, as the decision depends on whether it is an image
+/// index or a platform manifest.
 #[tracing::instrument(skip(state, request))]
 pub(crate) async fn v2_name_manifest_reference_get_head(
     state: &Extension<State>,
     Path((name, reference)): Path<(String, String)>,
     request: axum::http::Request<axum::body::Body>,
 ) -> Result<hyper::Response<hyper::Body>, StatusCode> {
-    let response = state
-        .snyk_api
-        .send_organization_projects_post(&state.http_client, format!("{}:{}", name, reference))
-        .await
-        .map_err(|error| {
-            tracing::error!(?error);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let namespace = oci::namespace(request.uri());
+    let (upstream, upstream_name) = state.oci_proxy.resolve(&name, namespace.as_deref());
+    let repository = format!("{}/{upstream_name}", upstream.base_address());
+    let method = request.method().clone();
+    let headers = request.headers().clone();
 
-    if let Err(error) = logic::admitted(&response) {
-        let body = serde_json::to_vec(&oci::Response {
-            errors: vec![oci::ResponseError {
-                code: "DENIED".to_string(),
-                message: error.to_string(),
-                details: None,
-            }],
-        })
-        .map_err(|error| {
-            tracing::error!(?error);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let response = match &state.cache {
+        Some(cache) => cache
+            .manifest(
+                &state.http_client,
+                &state.oci_proxy,
+                request,
+                &repository,
+                &reference,
+            )
+            .await
+            .map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_GATEWAY
+            })?,
+        None => v2_proxy(state, request).await?,
+    };
+
+    if response.status() != StatusCode::OK {
+        return Ok(response);
+    }
+
+    let digest = response
+        .headers()
+        .get("docker-content-digest")
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string)
+        .or_else(|| reference.contains(':').then(|| reference.clone()));
+
+    if v2_manifest_deleted(state, &repository, &reference, digest.as_deref()) {
+        return oci_error(
+            StatusCode::NOT_FOUND,
+            "MANIFEST_UNKNOWN",
+            "manifest unknown to registry",
+        );
+    }
+
+    let media_type = response
+        .headers()
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let is_index = oci::manifest::is_index(&media_type);
+
+    let (response, parent) = v2_manifest_lineage(
+        state,
+        &repository,
+        &reference,
+        digest.as_deref(),
+        is_index && method == axum::http::Method::GET,
+        response,
+    )
+    .await?;
+
+    let evaluate = match (state.admission_index, is_index) {
+        (AdmissionIndex::Index, true) | (AdmissionIndex::Platform, false) => true,
+        (AdmissionIndex::Platform, true) => false,
+        (AdmissionIndex::Index, false) => {
+            !matches!(&parent, Some(oci::Parent { index: Some(_), .. }))
+        }
+    };
+
+    if !evaluate {
+        return Ok(response);
+    }
+
+    // signatures of platform manifests may be attached to the image index instead.
+    let digests = digest
+        .into_iter()
+        .chain(parent.as_ref().and_then(|parent| parent.index.clone()))
+        .collect::<Vec<_>>();
+
+    // scanners know images by tag, so platform manifests are looked up through their parent.
+    let scanner_reference = parent.map_or_else(|| reference.clone(), |parent| parent.tag);
+
+    if v2_manifest_break_glass(state, &name, &reference, &digests, &headers) {
+        return Ok(response);
+    }
+
+    let admitted = v2_manifest_admitted(
+        state,
+        upstream,
+        &upstream_name,
+        &scanner_reference,
+        &digests,
+        headers.get(hyper::header::AUTHORIZATION),
+    )
+    .await?
+    .admitted;
+
+    if let Err(error) = admitted {
+        return v2_manifest_enforced(
+            state,
+            name,
+            reference,
+            digests.into_iter().next(),
+            principal::principal(&headers).as_deref(),
+            &error,
+            response,
+        );
+    }
+
+    Ok(response)
+}
+
+/// Evaluates the admission policy for a manifest.
+///
+/// `digests` are the digests of the manifest and of its parent image index, if any.
+async fn v2_manifest_admitted(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    scanner_reference: &str,
+    digests: &[String],
+    authorization: Option<&hyper::header::HeaderValue>,
+) -> Result<Admission, StatusCode> {
+    let (scanned, scan) =
+        v2_manifest_scanned(state, upstream, upstream_name, scanner_reference, digests).await?;
+
+    let admitted = match scanned {
+        Ok(()) => {
+            v2_manifest_evaluated(
+                state,
+                upstream,
+                upstream_name,
+                scanner_reference,
+                digests,
+                authorization,
+            )
+            .await?
+        }
+        denied => denied,
+    };
+
+    Ok(Admission { admitted, scan })
+}
+
+/// Evaluates the metadata, signature and attestation policies for a manifest.
+async fn v2_manifest_evaluated(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    scanner_reference: &str,
+    digests: &[String],
+    authorization: Option<&hyper::header::HeaderValue>,
+) -> Result<Result<(), logic::AdmitError>, StatusCode> {
+    if let Some(policy) = &state.metadata_policy {
+        let reference = digests.first().map_or(scanner_reference, String::as_str);
+
+        let admitted = policy
+            .evaluate(
+                &state.http_client,
+                upstream,
+                upstream_name,
+                reference,
+                authorization,
+            )
+            .await
+            .map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_GATEWAY
+            })?;
+
+        if let Err(error) = admitted {
+            return Ok(Err(error));
+        }
+    }
+
+    if let Some(verifier) = &state.signature_verifier {
+        let mut verified = Err(logic::AdmitError::NotSigned);
 
-        return hyper::Response::builder()
-            .status(StatusCode::FORBIDDEN)
-            .body(hyper::Body::from(body))
+        for digest in digests {
+            verified = verifier
+                .verify(
+                    &state.http_client,
+                    upstream,
+                    upstream_name,
+                    digest,
+                    authorization,
+                )
+                .await
+                .map_err(|error| {
+                    tracing::error!(?error);
+                    StatusCode::BAD_GATEWAY
+                })?;
+
+            if !matches!(verified, Err(logic::AdmitError::NotSigned)) {
+                break;
+            }
+        }
+
+        if let Err(error) = verified {
+            return Ok(Err(error));
+        }
+    }
+
+    if let Some(policy) = &state.attestation_policy {
+        let admitted = policy
+            .evaluate(
+                &state.http_client,
+                upstream,
+                upstream_name,
+                digests,
+                authorization,
+            )
+            .await
+            .map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_GATEWAY
+            })?;
+
+        if let Err(error) = admitted {
+            return Ok(Err(error));
+        }
+    }
+
+    Ok(Ok(()))
+}
+
+/// Evaluates the scan results of a manifest.
+///
+/// Pulls of quarantined manifests wait for the scanner to report on them, up to the quarantine
+/// wait.
+async fn v2_manifest_scanned(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    scanner_reference: &str,
+    digests: &[String],
+) -> Result<
+    (
+        Result<(), logic::AdmitError>,
+        snyk::organization_projects_post::Response,
+    ),
+    StatusCode,
+> {
+    let repository = format!("{}/{upstream_name}", upstream.base_address());
+    let quarantine = state
+        .quarantine
+        .as_ref()
+        .filter(|quarantine| quarantine.is_pending(&repository, digests));
+    let deadline = std::time::Instant::now()
+        + quarantine.map_or_else(Default::default, crate::quarantine::Quarantine::wait);
+
+    loop {
+        let scan = state
+            .snyk_api
+            .send_organization_projects_post(
+                &state.http_client,
+                format!("{upstream_name}:{scanner_reference}"),
+            )
+            .await
             .map_err(|error| {
                 tracing::error!(?error);
                 StatusCode::INTERNAL_SERVER_ERROR
-            });
+            })?;
+
+        // the scanner does not know newly pushed images until their scan completes.
+        match (
+            quarantine,
+            logic::admitted(&scan, &state.vulnerability_thresholds),
+        ) {
+            (Some(quarantine), Err(logic::AdmitError::NotMonitored)) => {
+                if std::time::Instant::now() + quarantine.poll_interval() > deadline {
+                    return Ok((
+                        Err(logic::AdmitError::Quarantined(
+                            quarantine.poll_interval().as_secs(),
+                        )),
+                        scan,
+                    ));
+                }
+
+                tokio::time::sleep(quarantine.poll_interval()).await;
+            }
+            (Some(quarantine), admitted) => {
+                quarantine.release(&repository, digests);
+                return Ok((admitted, scan));
+            }
+            (None, admitted) => return Ok((admitted, scan)),
+        }
     }
+}
 
-    v2_proxy(state, request).await
+/// Records the lineage of a manifest, returning its parent.
+///
+/// Manifests pulled by tag are recorded as their own parent, and the platform manifests of an
+/// image index inherit the parent of the image index.
+async fn v2_manifest_lineage(
+    state: &Extension<State>,
+    repository: &str,
+    reference: &str,
+    digest: Option<&str>,
+    is_index: bool,
+    response: hyper::Response<hyper::Body>,
+) -> Result<(hyper::Response<hyper::Body>, Option<oci::Parent>), StatusCode> {
+    let parent = if reference.contains(':') {
+        state.oci_lineage.get(repository, reference)
+    } else {
+        Some(oci::Parent {
+            tag: reference.to_string(),
+            index: None,
+        })
+    };
+
+    if let (Some(digest), Some(parent)) = (digest, &parent) {
+        if !reference.contains(':') {
+            state.oci_lineage.insert(repository, digest, parent.clone());
+        }
+    }
+
+    if !is_index {
+        return Ok((response, parent));
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::BAD_GATEWAY
+    })?;
+
+    let media_type = parts
+        .headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+
+    match (
+        oci::manifest::Manifest::from_slice(media_type, &body),
+        &parent,
+    ) {
+        (Ok(index), Some(parent)) => {
+            for manifest in index.manifests() {
+                state.oci_lineage.insert(
+                    repository,
+                    &manifest.digest,
+                    oci::Parent {
+                        tag: parent.tag.clone(),
+                        index: digest.map(ToString::to_string),
+                    },
+                );
+            }
+        }
+        (Ok(_), None) => {}
+        (Err(error), _) => tracing::warn!(?error, "Failed to parse image index"),
+    }
+
+    Ok((
+        hyper::Response::from_parts(parts, hyper::Body::from(body)),
+        parent,
+    ))
+}
+
+/// GET|HEAD /v2/:name/blobs/:digest
+///
+/// This endpoint is used by the OCI distribution specification proxy.
+#[tracing::instrument(skip(state, request))]
+pub(crate) async fn v2_name_blob_digest_get_head(
+    state: &Extension<State>,
+    Path((name, digest)): Path<(String, String)>,
+    request: axum::http::Request<axum::body::Body>,
+) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    if let Some(policy) = &state.delete_policy {
+        let namespace = oci::namespace(request.uri());
+        let (upstream, upstream_name) = state.oci_proxy.resolve(&name, namespace.as_deref());
+        let repository = format!("{}/{upstream_name}", upstream.base_address());
+
+        if policy.is_deleted(&repository, [digest.as_str()]) {
+            return oci_error(
+                StatusCode::NOT_FOUND,
+                "BLOB_UNKNOWN",
+                "blob unknown to registry",
+            );
+        }
+    }
+
+    match &state.cache {
+        Some(cache) => cache
+            .blob(&state.http_client, &state.oci_proxy, request, &digest)
+            .await
+            .map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_GATEWAY
+            }),
+        None => v2_proxy(state, request).await,
+    }
 }
 
 /// PUT /v2/:name/manifests/:reference
 ///
 /// This endpoint is used by the OCI distribution specification proxy.
+///
+/// The manifest is checked against the push policy before it is forwarded to the upstream.
 #[tracing::instrument(skip(state, request))]
 pub(crate) async fn v2_name_manifest_reference_put(
     state: &Extension<State>,
     Path((name, reference)): Path<(String, String)>,
     request: axum::http::Request<axum::body::Body>,
 ) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    let namespace = oci::namespace(request.uri());
+    let (upstream, upstream_name) = state.oci_proxy.resolve(&name, namespace.as_deref());
+
+    let request = match &state.push_policy {
+        Some(policy) => {
+            let (parts, body) = request.into_parts();
+            let body = hyper::body::to_bytes(body).await.map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_REQUEST
+            })?;
+
+            let media_type = parts
+                .headers
+                .get(hyper::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok());
+
+            let manifest = match oci::manifest::Manifest::from_slice(media_type, &body) {
+                Ok(manifest) => manifest,
+                Err(error) => {
+                    return oci_error(
+                        StatusCode::BAD_REQUEST,
+                        "MANIFEST_INVALID",
+                        &error.to_string(),
+                    )
+                }
+            };
+
+            if let Err(error) = policy.admitted(&reference, &manifest) {
+                return denied(&error);
+            }
+
+            let principal = principal::principal(&parts.headers);
+
+            if policy.is_immutable(&name, &reference, principal.as_deref()) {
+                let digest = format!("sha256:{:x}", sha2::Sha256::digest(&body));
+
+                let existing = upstream
+                    .manifest_digest(
+                        &state.http_client,
+                        &upstream_name,
+                        &reference,
+                        parts.headers.get(hyper::header::AUTHORIZATION),
+                    )
+                    .await
+                    .map_err(|error| {
+                        tracing::error!(?error);
+                        StatusCode::BAD_GATEWAY
+                    })?;
+
+                // pushing the same manifest again does not overwrite the tag.
+                if existing.is_some_and(|existing| existing != digest) {
+                    return denied(&logic::AdmitError::ImmutableTag(reference));
+                }
+            } else if principal.is_some() && policy.is_immutable(&name, &reference, None) {
+                tracing::info!(?principal, "Immutable tag overwrite allowed for principal");
+            }
+
+            axum::http::Request::from_parts(parts, hyper::Body::from(body))
+        }
+        None => request,
+    };
+
     let response = v2_proxy(state, request).await;
 
+    if let Ok(response) = &response {
+        if response.status() == StatusCode::CREATED {
+            v2_manifest_pushed(state, upstream, &upstream_name, &name, &reference, response);
+        }
+    }
+
     state
         .snyk_api
         .send_organization_integration_import_post(
             &state.http_client,
-            format!("{}:{}", name, reference),
+            format!("{upstream_name}:{reference}"),
         )
         .await
         .map_err(|error| {
@@ -132,6 +1091,425 @@ pub(crate) async fn v2_name_manifest_reference_put(
     response
 }
 
+/// Notifies the webhooks of a denied pull.
+///
+/// Pulls of quarantined manifests are not denials, as they are retried.
+fn v2_manifest_denied(
+    state: &Extension<State>,
+    name: String,
+    reference: String,
+    digest: Option<String>,
+    error: &logic::AdmitError,
+) {
+    if let (Some(notifier), false) = (
+        &state.notifier,
+        matches!(error, logic::AdmitError::Quarantined(_)),
+    ) {
+        notifier.notify(
+            &state.http_client,
+            &notification::Event {
+                kind: NotificationEvent::Denied,
+                repository: name,
+                reference,
+                digest,
+                message: error.to_string(),
+            },
+        );
+    }
+}
+
+/// Checks if the manifest was soft deleted, by tag or digest.
+fn v2_manifest_deleted(
+    state: &Extension<State>,
+    repository: &str,
+    reference: &str,
+    digest: Option<&str>,
+) -> bool {
+    state.delete_policy.as_ref().is_some_and(|policy| {
+        policy.is_deleted(repository, [Some(reference), digest].into_iter().flatten())
+    })
+}
+
+/// Checks if the break-glass token grants the pull of the manifest, bypassing admission.
+///
+/// Tokens sent with the pull credentials are presented through the break-glass header as well.
+///
+/// Bypasses are audited and notified to the webhooks.
+fn v2_manifest_break_glass(
+    state: &Extension<State>,
+    name: &str,
+    reference: &str,
+    digests: &[String],
+    headers: &hyper::HeaderMap,
+) -> bool {
+    let Some(token) = headers
+        .get(break_glass::HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let Some(claims) = state
+        .break_glass
+        .as_ref()
+        .and_then(|break_glass| break_glass.verify(token, name, digests))
+    else {
+        tracing::warn!(%name, %reference, "Break-glass token rejected");
+        return false;
+    };
+
+    tracing::warn!(%name, %reference, reason = %claims.reason, "Admission bypassed with break-glass token");
+    audit::record(
+        "pull",
+        name,
+        reference,
+        principal::principal(headers).as_deref(),
+        "break_glass",
+    );
+
+    if let Some(notifier) = &state.notifier {
+        notifier.notify(
+            &state.http_client,
+            &notification::Event {
+                kind: NotificationEvent::BreakGlass,
+                repository: name.to_string(),
+                reference: reference.to_string(),
+                digest: Some(claims.digest),
+                message: format!(
+                    "Admission bypassed with break-glass token: {}",
+                    claims.reason
+                ),
+            },
+        );
+    }
+
+    true
+}
+
+/// Enforces the denial of a pulled manifest according to the enforcement mode of the repository.
+///
+/// Manifests of repositories which are not enforced are proxied, recording the denial they would
+/// have been.
+fn v2_manifest_enforced(
+    state: &Extension<State>,
+    name: String,
+    reference: String,
+    digest: Option<String>,
+    principal: Option<&str>,
+    error: &logic::AdmitError,
+    mut response: hyper::Response<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    let mode = state
+        .enforcement_policy
+        .as_ref()
+        .map_or(EnforcementMode::Enforce, |policy| policy.mode(&name));
+
+    let mode_label = match mode {
+        EnforcementMode::Enforce => "enforce",
+        EnforcementMode::Warn => "warn",
+        EnforcementMode::Audit => "audit",
+    };
+
+    state.metrics.increment(
+        "container_registry_gateway_admission_denials_total",
+        &[("mode", mode_label)],
+    );
+
+    if mode == EnforcementMode::Enforce {
+        v2_manifest_denied(state, name, reference, digest, error);
+
+        return denied(error);
+    }
+
+    tracing::warn!(%name, %reference, %error, mode = mode_label, "Admission denial not enforced");
+    audit::record("pull", &name, &reference, principal, "would_deny");
+
+    if mode == EnforcementMode::Warn {
+        let warning = format!("299 - \"{}\"", error.to_string().replace('"', "\\\""));
+
+        if let Ok(warning) = hyper::header::HeaderValue::from_str(&warning) {
+            response
+                .headers_mut()
+                .append(hyper::header::WARNING, warning);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Records a pushed manifest, holding it in quarantine and notifying the webhooks.
+fn v2_manifest_pushed(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    upstream_name: &str,
+    name: &str,
+    reference: &str,
+    response: &hyper::Response<hyper::Body>,
+) {
+    let digest = response
+        .headers()
+        .get("docker-content-digest")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| reference.contains(':').then_some(reference));
+
+    if let (Some(quarantine), Some(digest)) = (&state.quarantine, digest) {
+        quarantine.hold(
+            &format!("{}/{upstream_name}", upstream.base_address()),
+            digest,
+            (!reference.contains(':')).then_some(reference),
+        );
+    }
+
+    if let Some(notifier) = &state.notifier {
+        let event = notification::Event {
+            kind: NotificationEvent::Pushed,
+            repository: name.to_string(),
+            reference: reference.to_string(),
+            digest: digest.map(ToString::to_string),
+            message: "Image pushed".to_string(),
+        };
+
+        notifier.notify(&state.http_client, &event);
+
+        if notifier.is_subscribed(name, NotificationEvent::Critical) {
+            notifier.watch_scan(
+                &state.http_client,
+                &state.snyk_api,
+                format!("{upstream_name}:{reference}"),
+                event,
+            );
+        }
+    }
+}
+
+/// DELETE /v2/:name/manifests/:reference
+/// DELETE /v2/:name/blobs/:digest
+///
+/// This endpoint is used by the OCI distribution specification proxy.
+///
+/// Deletions are subject to the delete protection rules, and are audited whatever the outcome.
+#[tracing::instrument(skip(state, request))]
+pub(crate) async fn v2_name_content_delete(
+    state: &Extension<State>,
+    Path((name, reference)): Path<(String, String)>,
+    request: axum::http::Request<axum::body::Body>,
+) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    let namespace = oci::namespace(request.uri());
+    let (upstream, upstream_name) = state.oci_proxy.resolve(&name, namespace.as_deref());
+    let repository = format!("{}/{upstream_name}", upstream.base_address());
+    let principal = principal::principal(request.headers());
+
+    let decision = state
+        .delete_policy
+        .as_ref()
+        .map_or(delete::Decision::Forward, |policy| {
+            policy.decide(&name, principal.as_deref())
+        });
+
+    match (decision, &state.delete_policy) {
+        (delete::Decision::Deny(error), _) => {
+            audit::record(
+                "delete",
+                &repository,
+                &reference,
+                principal.as_deref(),
+                "denied",
+            );
+
+            denied(&error)
+        }
+        (delete::Decision::Soft, Some(policy)) => {
+            // the deletion is not forwarded, the upstream must still grant the client access to
+            // the content, as the principal is not verified by the gateway.
+            let kind = if state
+                .oci_regex
+                .name_blob_digest
+                .is_match(request.uri().path())
+            {
+                "blobs"
+            } else {
+                "manifests"
+            };
+
+            if let Some(response) = v2_content_unauthorized(
+                state,
+                upstream,
+                &format!("/v2/{upstream_name}/{kind}/{reference}"),
+                request.headers(),
+            )
+            .await?
+            {
+                audit::record(
+                    "delete",
+                    &repository,
+                    &reference,
+                    principal.as_deref(),
+                    response.status().as_str(),
+                );
+
+                return Ok(response);
+            }
+
+            policy
+                .soft_delete(&repository, &reference, principal.as_deref())
+                .map_err(|error| {
+                    tracing::error!(?error);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+
+            audit::record(
+                "delete",
+                &repository,
+                &reference,
+                principal.as_deref(),
+                "soft_deleted",
+            );
+
+            hyper::Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(hyper::Body::empty())
+                .map_err(|error| {
+                    tracing::error!(?error);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })
+        }
+        (_, _) => {
+            let response = v2_proxy(state, request).await?;
+
+            audit::record(
+                "delete",
+                &repository,
+                &reference,
+                principal.as_deref(),
+                response.status().as_str(),
+            );
+
+            Ok(response)
+        }
+    }
+}
+
+/// Checks the client is granted access to the content at `path` by the `upstream`, on behalf of
+/// its credentials.
+///
+/// Returns the response to send back to the client if access is not granted, carrying the
+/// authentication challenge of the upstream.
+async fn v2_content_unauthorized(
+    state: &Extension<State>,
+    upstream: &oci::Upstream,
+    path: &str,
+    headers: &hyper::HeaderMap,
+) -> Result<Option<hyper::Response<hyper::Body>>, StatusCode> {
+    let response = upstream
+        .authorize(
+            &state.http_client,
+            path,
+            headers.get(hyper::header::AUTHORIZATION),
+        )
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    if response.status().is_success() {
+        return Ok(None);
+    }
+
+    let mut unauthorized = hyper::Response::builder().status(response.status());
+
+    if let Some(challenge) = response.headers().get(hyper::header::WWW_AUTHENTICATE) {
+        unauthorized = unauthorized.header(hyper::header::WWW_AUTHENTICATE, challenge);
+    }
+
+    unauthorized
+        .body(hyper::Body::empty())
+        .map(Some)
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// GET /v2/:name/referrers/:digest
+///
+/// This endpoint is used by the OCI distribution specification proxy.
+///
+/// Referrers of upstreams without the referrers API are served from the referrers tag schema, and
+/// the `artifactType` filter is applied if the upstream did not apply it.
+#[tracing::instrument(skip(state, request))]
+pub(crate) async fn v2_name_referrers_digest_get(
+    state: &Extension<State>,
+    Path((name, digest)): Path<(String, String)>,
+    request: axum::http::Request<axum::body::Body>,
+) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    let namespace = oci::namespace(request.uri());
+    let artifact_type = oci::query(request.uri(), "artifactType");
+    let (upstream, upstream_name) = state.oci_proxy.resolve(&name, namespace.as_deref());
+    let authorization = request.headers().get(hyper::header::AUTHORIZATION).cloned();
+
+    let response = v2_proxy(state, request).await?;
+
+    let filtered = response
+        .headers()
+        .get(OCI_FILTERS_APPLIED)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|filters| {
+            filters
+                .split(',')
+                .any(|filter| filter.trim() == "artifactType")
+        });
+
+    let mut index = match response.status() {
+        StatusCode::OK if artifact_type.is_none() || filtered => return Ok(response),
+        StatusCode::OK => {
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .map_err(|error| {
+                    tracing::error!(?error);
+                    StatusCode::BAD_GATEWAY
+                })?;
+
+            serde_json::from_slice::<oci::manifest::ImageIndex>(&body).map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_GATEWAY
+            })?
+        }
+        // the upstream does not support the referrers API.
+        StatusCode::NOT_FOUND => upstream
+            .referrers_tag(
+                &state.http_client,
+                &upstream_name,
+                &digest,
+                authorization.as_ref(),
+            )
+            .await
+            .map_err(|error| {
+                tracing::error!(?error);
+                StatusCode::BAD_GATEWAY
+            })?,
+        _ => return Ok(response),
+    };
+
+    let mut response = hyper::Response::builder()
+        .header(hyper::header::CONTENT_TYPE, oci::manifest::OCI_IMAGE_INDEX);
+
+    if let Some(artifact_type) = &artifact_type {
+        index.retain_artifact_type(artifact_type);
+        response = response.header(OCI_FILTERS_APPLIED, "artifactType");
+    }
+
+    let body = serde_json::to_vec(&index).map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    response.body(hyper::Body::from(body)).map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
 /// Fallback route for /v2/* if no routes match the incoming request.
 ///
 /// This endpoint is used by the OCI distribution specification proxy.
@@ -148,3 +1526,54 @@ pub(crate) async fn v2_proxy(
             StatusCode::INTERNAL_SERVER_ERROR
         })
 }
+
+/// Builds a response denying the request.
+///
+/// The error is returned in the OCI distribution specification error format.
+///
+/// Quarantined images are denied with a retriable error instead.
+fn denied(error: &logic::AdmitError) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    if let logic::AdmitError::Quarantined(retry_after) = error {
+        let mut response = oci_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            "TOOMANYREQUESTS",
+            &error.to_string(),
+        )?;
+
+        response
+            .headers_mut()
+            .insert(hyper::header::RETRY_AFTER, (*retry_after).into());
+
+        return Ok(response);
+    }
+
+    oci_error(StatusCode::FORBIDDEN, "DENIED", &error.to_string())
+}
+
+/// Builds an error response in the OCI distribution specification error format.
+fn oci_error(
+    status: StatusCode,
+    code: &str,
+    message: &str,
+) -> Result<hyper::Response<hyper::Body>, StatusCode> {
+    let body = serde_json::to_vec(&oci::Response {
+        errors: vec![oci::ResponseError {
+            code: code.to_string(),
+            message: message.to_string(),
+            details: None,
+        }],
+    })
+    .map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    hyper::Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(body))
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
@@ -0,0 +1,156 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use zeroize::Zeroizing;
+
+/// A secret value, redacted in `Debug` and serialized output and zeroized in memory when dropped.
+#[derive(Clone)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    /// Creates a new `Secret` instance.
+    #[must_use]
+    pub fn new(value: impl Into<String>) -> Secret {
+        Secret(Zeroizing::new(value.into()))
+    }
+
+    /// Returns the value of the secret.
+    #[must_use]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the `Authorization` header value of the secret for the `scheme`, marked as
+    /// sensitive.
+    ///
+    /// # Errors
+    ///
+    /// If the secret is not a valid header value, an error is returned.
+    pub(crate) fn authorization(&self, scheme: &str) -> crate::Result<hyper::header::HeaderValue> {
+        let value = Zeroizing::new(format!("{scheme} {}", self.expose()));
+
+        let mut header_value = hyper::header::HeaderValue::from_str(&value)?;
+        header_value.set_sensitive(true);
+
+        Ok(header_value)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[redacted]")
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Secret {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Secret, D::Error> {
+        String::deserialize(deserializer).map(Secret::new)
+    }
+}
+
+impl serde::Serialize for Secret {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("[redacted]")
+    }
+}
+
+/// Where a secret is sourced from, either its value or a file holding it.
+///
+/// Files are read again whenever they change, so secrets mounted by Kubernetes can be rotated
+/// without a restart.
+#[derive(Clone, Debug)]
+pub(crate) enum Source {
+    Value(Secret),
+    File(Arc<File>),
+}
+
+#[derive(Debug)]
+pub(crate) struct File {
+    path: PathBuf,
+    read: Mutex<Read>,
+}
+
+#[derive(Debug)]
+struct Read {
+    modified: Option<SystemTime>,
+    len: u64,
+    secret: Secret,
+}
+
+impl Source {
+    /// Creates a new `Source` instance from the `value` of a secret, or else its `file`.
+    ///
+    /// # Errors
+    ///
+    /// If neither is given, or the file cannot be read, an error is returned.
+    pub(crate) fn new(value: Option<&Secret>, file: Option<&str>) -> crate::Result<Source> {
+        match (value, file) {
+            (Some(value), _) => Ok(Source::Value(value.clone())),
+            (None, Some(file)) => {
+                let path = PathBuf::from(file);
+                let read = File::read(&path)
+                    .map_err(|error| format!("Failed to read secret {file}, {error}"))?;
+
+                Ok(Source::File(Arc::new(File {
+                    path,
+                    read: Mutex::new(read),
+                })))
+            }
+            (None, None) => Err("Missing secret".into()),
+        }
+    }
+
+    /// Returns the current value of the secret.
+    ///
+    /// If the file of the secret changed but cannot be read, the previous value is returned.
+    pub(crate) fn get(&self) -> Secret {
+        match self {
+            Source::Value(secret) => secret.clone(),
+            Source::File(file) => file.get(),
+        }
+    }
+}
+
+impl File {
+    fn get(&self) -> Secret {
+        let mut read = self.read.lock().unwrap();
+
+        let changed = std::fs::metadata(&self.path).is_ok_and(|metadata| {
+            metadata.modified().ok() != read.modified || metadata.len() != read.len
+        });
+
+        if changed {
+            match File::read(&self.path) {
+                Ok(reread) => {
+                    tracing::info!(path = %self.path.display(), "Secret rotated");
+                    *read = reread;
+                }
+                Err(error) => {
+                    tracing::warn!(path = %self.path.display(), %error, "Failed to read secret");
+                }
+            }
+        }
+
+        read.secret.clone()
+    }
+
+    fn read(path: &std::path::Path) -> std::io::Result<Read> {
+        let metadata = std::fs::metadata(path)?;
+        let contents = Zeroizing::new(std::fs::read_to_string(path)?);
+
+        Ok(Read {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            // files written by hand usually end with a newline, which is not part of the secret.
+            secret: Secret::new(contents.trim_end_matches(['\r', '\n'])),
+        })
+    }
+}
+
+/// Compares the secrets in a time independent of their contents.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
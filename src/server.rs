@@ -1,11 +1,23 @@
-use std::{future::Future, net::TcpListener};
+use std::{
+    future::Future,
+    net::TcpListener,
+    sync::{Arc, RwLock},
+};
 
 use axum::{
-    routing::{any, get},
+    extract::ConnectInfo,
+    http::{HeaderValue, Request},
+    middleware::{self, Next},
+    response::Response,
+    routing::{any, get, post},
     Extension, Router, Server,
 };
+use tokio::sync::mpsc::Receiver;
+
+use crate::{break_glass, configuration, principal, route, state, tls};
 
-use crate::{configuration, http, oci, route, snyk, state};
+/// The state shared by the handlers, swapped as a whole when the configuration is reloaded.
+type Shared = Arc<RwLock<state::State>>;
 
 /// # Errors
 ///
@@ -14,36 +26,156 @@ pub async fn run(
     tcp_listener: TcpListener,
     shutdown_signal: impl Future<Output = ()>,
     configuration: configuration::Configuration,
+) -> crate::Result<()> {
+    let (_, reloads) = tokio::sync::mpsc::channel(1);
+
+    run_with_reload(tcp_listener, shutdown_signal, configuration, reloads).await
+}
+
+/// Runs the server, applying every configuration received from `reloads` without restarting.
+///
+/// A configuration is only applied once the state built from it is valid, otherwise the server
+/// keeps the previous configuration. Changes to the `http_server` section require a restart,
+/// though the TLS certificate is reloaded whenever its files change.
+///
+/// # Errors
+///
+/// Returns `Err` if the server fails to start.
+pub async fn run_with_reload(
+    tcp_listener: TcpListener,
+    shutdown_signal: impl Future<Output = ()>,
+    configuration: configuration::Configuration,
+    reloads: Receiver<configuration::Configuration>,
 ) -> crate::Result<()> {
     let socket_addr = tcp_listener.local_addr()?;
 
-    let state = state::State {
-        http_client: http::client(),
-        oci_proxy: oci::Proxy::new(configuration.oci.base_address),
-        oci_regex: oci::Regex::default(),
-        snyk_api: snyk::Api::new(
-            configuration.snyk.base_address,
-            configuration.snyk.api_key,
-            configuration.snyk.organization_id,
-            configuration.snyk.integration_id,
-        ),
-    };
+    let tls = configuration.http_server.tls.clone();
+
+    let shared: Shared = Arc::new(RwLock::new(state::State::new(configuration.clone())?));
+
+    let reload = tokio::spawn(reload(shared.clone(), configuration, reloads));
 
     let app = Router::new()
+        .route("/admission/mutate", post(route::admission_mutate_post))
+        .route("/admission/validate", post(route::admission_validate_post))
+        .route("/break-glass", post(route::break_glass_post))
+        .route("/delete/restore", post(route::delete_restore_post))
         .route("/health/liveness", get(route::health_liveness_get))
         .route("/health/readiness", get(route::health_readiness_get))
+        .route("/metrics", get(route::metrics_get))
+        .route("/quarantine", get(route::quarantine_get))
+        .route("/registry/events", post(route::registry_events_post))
         .route("/v2/*path", any(route::v2_routes))
-        .layer(Extension(state));
+        .layer(middleware::from_fn(current_state))
+        .layer(middleware::from_fn(client_certificate))
+        .layer(middleware::from_fn(break_glass_credentials))
+        .layer(Extension(shared));
+
+    let outcome = if let Some(tls) = tls {
+        let server = Server::builder(tls::incoming(tcp_listener, &tls)?)
+            .serve(app.into_make_service_with_connect_info::<tls::ClientCertificate>())
+            .with_graceful_shutdown(shutdown_signal);
+
+        tracing::info!(%socket_addr, "Server started with TLS");
 
-    let server = Server::from_tcp(tcp_listener)?
-        .serve(app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal);
+        server.await
+    } else {
+        let server = Server::from_tcp(tcp_listener)?
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(shutdown_signal);
 
-    tracing::info!(%socket_addr, "Server started");
+        tracing::info!(%socket_addr, "Server started");
+
+        server.await
+    };
 
-    server.await?;
+    reload.abort();
+
+    outcome?;
 
     tracing::info!("Server stopped");
 
     Ok(())
 }
+
+/// Provides the handlers with the current state, for the whole of the request.
+async fn current_state<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let state = request
+        .extensions()
+        .get::<Shared>()
+        .map(|shared| shared.read().unwrap().clone());
+
+    if let Some(state) = state {
+        request.extensions_mut().insert(state);
+    }
+
+    next.run(request).await
+}
+
+/// Presents a break-glass token sent with the pull credentials through the break-glass header.
+async fn break_glass_credentials<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    break_glass::from_credentials(request.headers_mut());
+
+    next.run(request).await
+}
+
+/// Sets the principal of the request to the subject of the verified client certificate, if any.
+async fn client_certificate<B>(mut request: Request<B>, next: Next<B>) -> Response {
+    let subject = request
+        .extensions()
+        .get::<ConnectInfo<tls::ClientCertificate>>()
+        .and_then(|ConnectInfo(client_certificate)| client_certificate.subject.as_deref())
+        .and_then(|subject| HeaderValue::from_str(subject).ok());
+
+    let headers = request.headers_mut();
+    headers.remove(principal::CLIENT_CERTIFICATE);
+
+    if let Some(subject) = subject {
+        headers.insert(principal::CLIENT_CERTIFICATE, subject);
+    }
+
+    next.run(request).await
+}
+
+async fn reload(
+    shared: Shared,
+    mut configuration: configuration::Configuration,
+    mut reloads: Receiver<configuration::Configuration>,
+) {
+    while let Some(reloaded) = reloads.recv().await {
+        let changed = match configuration::diff(&configuration, &reloaded) {
+            Ok(changed) => changed,
+            Err(error) => {
+                tracing::error!(?error, "Failed to compare configurations");
+                continue;
+            }
+        };
+
+        if changed.is_empty() {
+            tracing::info!("Configuration unchanged");
+            continue;
+        }
+
+        let previous = shared.read().unwrap().clone();
+
+        match previous.reloaded(reloaded.clone()) {
+            Ok(state) => {
+                *shared.write().unwrap() = state;
+                configuration = reloaded;
+
+                tracing::info!(?changed, "Configuration reloaded");
+
+                if changed.iter().any(|key| key.starts_with("http_server.")) {
+                    tracing::warn!("Changes to http_server require a restart");
+                }
+            }
+            Err(error) => {
+                tracing::error!(
+                    ?error,
+                    ?changed,
+                    "Invalid configuration, keeping the previous one"
+                );
+            }
+        }
+    }
+}
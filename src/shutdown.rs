@@ -1,4 +1,8 @@
 /// Receives the shutdown signal, waiting if necessary.
+///
+/// # Panics
+///
+/// Panics if the signal handlers cannot be installed.
 pub async fn recv() {
     let control_c = async {
         tokio::signal::ctrl_c()
@@ -29,13 +33,13 @@ pub async fn recv() {
     let sigterm = std::future::pending::<()>();
 
     let signal = tokio::select! {
-        _ = control_c => {
+        () = control_c => {
             "Ctrl+C"
         }
-        _ = sigint => {
+        () = sigint => {
             "SIGINT"
         }
-        _ = sigterm => {
+        () = sigterm => {
             "SIGTERM"
         }
     };
@@ -0,0 +1,195 @@
+use base64::Engine as _;
+use p256::{
+    ecdsa::{signature::Verifier as _, Signature, VerifyingKey},
+    pkcs8::DecodePublicKey as _,
+};
+
+use crate::{logic::AdmitError, oci};
+
+/// Annotation of a cosign signature layer, containing the base64 encoded signature.
+const SIGNATURE_ANNOTATION: &str = "dev.cosignproject.cosign/signature";
+
+/// Artifact type of a cosign signature, when attached through the referrers API.
+const SIGNATURE_ARTIFACT_TYPE: &str = "application/vnd.dev.cosign.artifact.sig.v1+json";
+
+/// Verifies cosign signatures against a set of trusted public keys.
+#[derive(Clone)]
+pub(crate) struct Verifier {
+    keys: Vec<VerifyingKey>,
+}
+
+/// <https://github.com/secure-systems-lab/dsse/blob/master/envelope.md>
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    payload_type: String,
+    payload: String,
+    signatures: Vec<EnvelopeSignature>,
+}
+
+#[derive(serde::Deserialize)]
+struct EnvelopeSignature {
+    sig: String,
+}
+
+#[derive(serde::Deserialize)]
+struct SimpleSigning {
+    critical: SimpleSigningCritical,
+}
+
+#[derive(serde::Deserialize)]
+struct SimpleSigningCritical {
+    image: SimpleSigningImage,
+}
+
+#[derive(serde::Deserialize)]
+struct SimpleSigningImage {
+    #[serde(rename = "docker-manifest-digest")]
+    docker_manifest_digest: String,
+}
+
+impl Verifier {
+    /// Creates a new `Verifier` instance.
+    ///
+    /// # Errors
+    ///
+    /// If any of the PEM encoded ECDSA P-256 public keys is malformed, an error is returned.
+    pub(crate) fn new<'a>(
+        public_keys: impl IntoIterator<Item = &'a str>,
+    ) -> crate::Result<Verifier> {
+        Ok(Verifier {
+            keys: public_keys
+                .into_iter()
+                .map(VerifyingKey::from_public_key_pem)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Verifies the signatures of the manifest `digest`.
+    ///
+    /// Signatures are looked up through the `sha256-<digest>.sig` tag convention and the
+    /// referrers API.
+    pub(crate) async fn verify(
+        &self,
+        client: &crate::http::Client,
+        upstream: &oci::Upstream,
+        name: &str,
+        digest: &str,
+        authorization: Option<&hyper::header::HeaderValue>,
+    ) -> crate::Result<Result<(), AdmitError>> {
+        let mut manifests = Vec::new();
+
+        if let Some(manifest) = upstream
+            .manifest(client, name, &signature_tag(digest, "sig"), authorization)
+            .await?
+        {
+            manifests.push(manifest);
+        }
+
+        let referrers = upstream
+            .referrers(
+                client,
+                name,
+                digest,
+                Some(SIGNATURE_ARTIFACT_TYPE),
+                authorization,
+            )
+            .await?;
+
+        for referrer in referrers.manifests {
+            if let Some(manifest) = upstream
+                .manifest(client, name, &referrer.digest, authorization)
+                .await?
+            {
+                manifests.push(manifest);
+            }
+        }
+
+        let mut signed = false;
+
+        for layer in manifests.iter().flat_map(oci::manifest::Manifest::layers) {
+            let Some(signature) = layer.annotations.get(SIGNATURE_ANNOTATION) else {
+                continue;
+            };
+
+            signed = true;
+
+            let payload = upstream
+                .blob(client, name, &layer.digest, authorization)
+                .await?;
+
+            if self.verify_payload(&payload, signature, digest) {
+                return Ok(Ok(()));
+            }
+        }
+
+        if signed {
+            Ok(Err(AdmitError::InvalidSignature))
+        } else {
+            Ok(Err(AdmitError::NotSigned))
+        }
+    }
+
+    /// Verifies a signed payload is for the manifest `digest`, and is signed by a trusted key.
+    pub(crate) fn verify_payload(&self, payload: &[u8], signature: &str, digest: &str) -> bool {
+        let Ok(simple_signing) = serde_json::from_slice::<SimpleSigning>(payload) else {
+            return false;
+        };
+
+        if simple_signing.critical.image.docker_manifest_digest != digest {
+            return false;
+        }
+
+        self.verify_signature(payload, signature)
+    }
+
+    /// Verifies a DSSE envelope is signed by a trusted key.
+    ///
+    /// Returns the payload type and payload of the envelope, if verified.
+    pub(crate) fn verify_envelope(&self, envelope: &[u8]) -> Option<(String, Vec<u8>)> {
+        let envelope = serde_json::from_slice::<Envelope>(envelope).ok()?;
+
+        let payload = base64::engine::general_purpose::STANDARD
+            .decode(&envelope.payload)
+            .ok()?;
+
+        // signatures are over the pre-authentication encoding of the payload.
+        let pae = [
+            format!(
+                "DSSEv1 {} {} {} ",
+                envelope.payload_type.len(),
+                envelope.payload_type,
+                payload.len()
+            )
+            .as_bytes(),
+            &payload,
+        ]
+        .concat();
+
+        envelope
+            .signatures
+            .iter()
+            .any(|signature| self.verify_signature(&pae, &signature.sig))
+            .then_some((envelope.payload_type, payload))
+    }
+
+    /// Verifies a base64 encoded, DER encoded ECDSA signature of the payload.
+    pub(crate) fn verify_signature(&self, payload: &[u8], signature: &str) -> bool {
+        let Ok(signature) = base64::engine::general_purpose::STANDARD.decode(signature) else {
+            return false;
+        };
+
+        let Ok(signature) = Signature::from_der(&signature) else {
+            return false;
+        };
+
+        self.keys
+            .iter()
+            .any(|key| key.verify(payload, &signature).is_ok())
+    }
+}
+
+/// Returns the tag cosign attaches artifacts of the `suffix` kind to the manifest `digest` with.
+pub(crate) fn signature_tag(digest: &str, suffix: &str) -> String {
+    format!("{}.{suffix}", digest.replace(':', "-"))
+}
@@ -1,10 +1,13 @@
+use crate::secret;
+
 mod organization_integration_import_post;
 pub(crate) mod organization_projects_post;
 
 #[derive(Clone)]
+#[allow(clippy::struct_field_names)]
 pub(crate) struct Api {
     base_address: String,
-    api_key: String,
+    api_key: secret::Source,
     organization_id: String,
     integration_id: String,
 }
@@ -16,7 +19,7 @@ impl<T: std::fmt::Debug> std::error::Error for ApiError<T> {}
 
 impl<T: std::fmt::Debug> std::fmt::Display for ApiError<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{self:?}")
     }
 }
 
@@ -24,13 +27,13 @@ impl Api {
     /// Creates a new `Api` instance.
     pub(crate) fn new(
         base_address: impl Into<String>,
-        api_key: impl Into<String>,
+        api_key: secret::Source,
         organization_id: impl Into<String>,
         integration_id: impl Into<String>,
     ) -> Api {
         Api {
             base_address: base_address.into(),
-            api_key: api_key.into(),
+            api_key,
             organization_id: organization_id.into(),
             integration_id: integration_id.into(),
         }
@@ -48,7 +51,7 @@ impl Api {
 
         let request = Request {
             base_address: self.base_address.clone(),
-            api_key: self.api_key.clone(),
+            api_key: self.api_key.get(),
             organization_id: self.organization_id.clone(),
             integration_id: self.integration_id.clone(),
             body: RequestBody {
@@ -71,7 +74,7 @@ impl Api {
 
         let request = Request {
             base_address: self.base_address.clone(),
-            api_key: self.api_key.clone(),
+            api_key: self.api_key.get(),
             organization_id: self.organization_id.clone(),
             body: RequestBody {
                 filters: RequestBodyFilters { name: name.into() },
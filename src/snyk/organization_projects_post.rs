@@ -1,8 +1,9 @@
 use super::ApiError;
+use crate::secret::Secret;
 
 pub(crate) struct Request {
     pub(crate) base_address: String,
-    pub(crate) api_key: String,
+    pub(crate) api_key: Secret,
     pub(crate) organization_id: String,
     pub(crate) body: RequestBody,
 }
@@ -60,7 +61,7 @@ impl TryFrom<Request> for hyper::Request<hyper::Body> {
             ))
             .header(
                 hyper::header::AUTHORIZATION,
-                format!("token {}", this.api_key),
+                this.api_key.authorization("token")?,
             )
             .header(hyper::header::CONTENT_TYPE, "application/json")
             .body(hyper::body::Body::from(serde_json::to_vec(&this.body)?))
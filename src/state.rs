@@ -1,7 +1,232 @@
+use std::time::Duration;
+
+use crate::{
+    attestation, break_glass, cache, configuration, delete, enforcement, http, kubernetes,
+    metadata, metrics, notification, oci, push, quarantine, registry_event, secret, signature,
+    snyk,
+};
+
 #[derive(Clone)]
 pub(crate) struct State {
+    pub(crate) admission_index: crate::configuration::AdmissionIndex,
+    pub(crate) attestation_policy: Option<crate::attestation::Policy>,
+    pub(crate) break_glass: Option<crate::break_glass::BreakGlass>,
+    pub(crate) cache: Option<crate::cache::Cache>,
+    pub(crate) delete_policy: Option<crate::delete::Policy>,
+    pub(crate) enforcement_policy: Option<crate::enforcement::Policy>,
     pub(crate) http_client: crate::http::Client,
+    pub(crate) kubernetes_webhook: Option<crate::kubernetes::Webhook>,
+    pub(crate) metadata_policy: Option<crate::metadata::Policy>,
+    pub(crate) metrics: crate::metrics::Metrics,
+    pub(crate) notifier: Option<crate::notification::Notifier>,
+    pub(crate) oci_lineage: crate::oci::Lineage,
     pub(crate) oci_proxy: crate::oci::Proxy,
     pub(crate) oci_regex: crate::oci::Regex,
+    pub(crate) push_policy: Option<crate::push::Policy>,
+    pub(crate) quarantine: Option<crate::quarantine::Quarantine>,
+    pub(crate) registry_event_receiver: Option<crate::registry_event::Receiver>,
+    pub(crate) signature_verifier: Option<crate::signature::Verifier>,
     pub(crate) snyk_api: crate::snyk::Api,
+    pub(crate) vulnerability_thresholds: crate::logic::Thresholds,
+}
+
+impl State {
+    /// Creates a new `State` instance from the configuration.
+    ///
+    /// # Errors
+    ///
+    /// If any of the policies is malformed, an error is returned.
+    pub(crate) fn new(configuration: configuration::Configuration) -> crate::Result<State> {
+        let signature_verifier = configuration
+            .signature
+            .map(|configuration| {
+                signature::Verifier::new(configuration.public_keys.iter().map(String::as_str))
+            })
+            .transpose()?;
+
+        let attestation_policy = match (configuration.attestation, &signature_verifier) {
+            (Some(configuration), Some(signature_verifier)) => Some(attestation::Policy::new(
+                signature_verifier.clone(),
+                configuration,
+            )),
+            (Some(_), None) => {
+                return Err("Attestation verification requires signature public keys".into())
+            }
+            (None, _) => None,
+        };
+
+        Ok(State {
+            admission_index: configuration.admission.index,
+            attestation_policy,
+            break_glass: configuration
+                .break_glass
+                .as_ref()
+                .map(break_glass::BreakGlass::new)
+                .transpose()?,
+            cache: configuration
+                .cache
+                .map(|configuration| {
+                    cache::Cache::new(
+                        configuration.directory,
+                        configuration.max_size,
+                        Duration::from_secs(configuration.manifest_ttl),
+                    )
+                })
+                .transpose()?,
+            delete_policy: configuration.delete.map(delete::Policy::new).transpose()?,
+            enforcement_policy: configuration
+                .enforcement
+                .as_ref()
+                .map(enforcement::Policy::new)
+                .transpose()?,
+            http_client: http::client(),
+            kubernetes_webhook: configuration
+                .kubernetes
+                .as_ref()
+                .map(kubernetes::Webhook::new),
+            metadata_policy: configuration.metadata.map(metadata::Policy::new),
+            metrics: metrics::Metrics::default(),
+            notifier: configuration
+                .notification
+                .map(notification::Notifier::new)
+                .transpose()?,
+            oci_lineage: oci::Lineage::default(),
+            oci_proxy: proxy(configuration.oci)?,
+            oci_regex: oci::Regex::default(),
+            push_policy: configuration.push.map(push::Policy::new).transpose()?,
+            quarantine: configuration
+                .quarantine
+                .as_ref()
+                .map(quarantine::Quarantine::new),
+            registry_event_receiver: configuration
+                .registry_events
+                .as_ref()
+                .map(registry_event::Receiver::new)
+                .transpose()?,
+            signature_verifier,
+            snyk_api: snyk::Api::new(
+                configuration.snyk.base_address,
+                secret::Source::new(
+                    configuration.snyk.api_key.as_ref(),
+                    configuration.snyk.api_key_file.as_deref(),
+                )?,
+                configuration.snyk.organization_id,
+                configuration.snyk.integration_id,
+            ),
+            vulnerability_thresholds: configuration
+                .vulnerability
+                .as_ref()
+                .map(Into::into)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Creates a new `State` instance from the reloaded configuration.
+    ///
+    /// Runtime data, such as metrics, lineage, pending quarantines, soft deletions, notification
+    /// deduplication and the cache index when its directory is unchanged, are carried over from
+    /// the previous state.
+    ///
+    /// # Errors
+    ///
+    /// If any of the policies is malformed, an error is returned.
+    pub(crate) fn reloaded(
+        &self,
+        mut configuration: configuration::Configuration,
+    ) -> crate::Result<State> {
+        let cache = self
+            .cache
+            .as_ref()
+            .zip(configuration.cache.as_ref())
+            .filter(|(cache, configuration)| cache.is_in(&configuration.directory))
+            .map(|(cache, configuration)| {
+                cache.reconfigured(
+                    configuration.max_size,
+                    Duration::from_secs(configuration.manifest_ttl),
+                )
+            });
+
+        if cache.is_some() {
+            configuration.cache = None;
+        }
+
+        let mut state = State::new(configuration)?;
+
+        if cache.is_some() {
+            state.cache = cache;
+        }
+
+        if let (Some(delete_policy), Some(previous)) =
+            (&mut state.delete_policy, &self.delete_policy)
+        {
+            delete_policy.carry_over(previous);
+        }
+
+        if let (Some(notifier), Some(previous)) = (&mut state.notifier, &self.notifier) {
+            notifier.carry_over(previous);
+        }
+
+        if let (Some(quarantine), Some(previous)) = (&mut state.quarantine, &self.quarantine) {
+            quarantine.carry_over(previous);
+        }
+
+        state.http_client = self.http_client.clone();
+        state.metrics = self.metrics.clone();
+        state.oci_lineage = self.oci_lineage.clone();
+
+        Ok(state)
+    }
+}
+
+/// Creates the proxy to the upstreams of the configuration.
+///
+/// # Errors
+///
+/// If the password of an upstream cannot be read, an error is returned.
+fn proxy(configuration: configuration::Oci) -> crate::Result<oci::Proxy> {
+    let upstreams = configuration
+        .upstreams
+        .into_iter()
+        .map(|upstream| {
+            Ok(oci::Upstream::new(
+                upstream.prefix,
+                upstream.registry,
+                upstream.base_address,
+                credentials(
+                    upstream.username,
+                    upstream.password.as_ref(),
+                    upstream.password_file.as_deref(),
+                )?,
+            ))
+        })
+        .collect::<crate::Result<Vec<_>>>()?;
+
+    let upstream = oci::Upstream::new(
+        None,
+        configuration.registry,
+        configuration.base_address,
+        credentials(
+            configuration.username,
+            configuration.password.as_ref(),
+            configuration.password_file.as_deref(),
+        )?,
+    );
+
+    Ok(oci::Proxy::new(upstream, upstreams, configuration.mirror))
+}
+
+/// Creates the credentials of an upstream, if it has a `username`.
+fn credentials(
+    username: Option<String>,
+    password: Option<&configuration::Secret>,
+    password_file: Option<&str>,
+) -> crate::Result<Option<oci::Credentials>> {
+    username
+        .map(|username| {
+            Ok(oci::Credentials::new(
+                username,
+                secret::Source::new(password, password_file)?,
+            ))
+        })
+        .transpose()
 }
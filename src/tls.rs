@@ -0,0 +1,306 @@
+use std::{
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_rustls::server::TlsStream;
+use x509_cert::der::{
+    asn1::{ObjectIdentifier, PrintableStringRef, Utf8StringRef},
+    Decode as _,
+};
+
+use crate::configuration;
+
+/// Duration clients are given to complete the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Object identifier of the common name attribute.
+const COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+
+/// Connections accepted once their TLS handshake completed.
+pub(crate) struct Incoming(mpsc::Receiver<TlsStream<TcpStream>>);
+
+/// The client certificate presented on a connection, verified against the client CA.
+#[derive(Clone)]
+pub(crate) struct ClientCertificate {
+    /// Common name of the subject of the certificate.
+    pub(crate) subject: Option<String>,
+}
+
+/// Serves the certificate, loading it again whenever its files change so it can be renewed
+/// without a restart.
+struct Resolver {
+    certificate: PathBuf,
+    key: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+struct Loaded {
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    certified_key: Arc<rustls::sign::CertifiedKey>,
+}
+
+/// Accepts TLS connections on the `tcp_listener`.
+///
+/// The handshakes are completed in the background, so slow or failing clients do not hold up
+/// other connections.
+///
+/// # Errors
+///
+/// If the certificate, the key or the client CA cannot be loaded, an error is returned.
+pub(crate) fn incoming(
+    tcp_listener: std::net::TcpListener,
+    configuration: &configuration::Tls,
+) -> crate::Result<Incoming> {
+    let resolver = Resolver::new(&configuration.certificate, &configuration.key)?;
+
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match &configuration.client_ca {
+        Some(client_ca) => {
+            let mut roots = rustls::RootCertStore::empty();
+
+            for certificate in certificates(&PathBuf::from(client_ca))? {
+                roots.add(&certificate)?;
+            }
+
+            builder.with_client_cert_verifier(if configuration.client_certificate_required {
+                rustls::server::AllowAnyAuthenticatedClient::new(roots)
+            } else {
+                rustls::server::AllowAnyAnonymousOrAuthenticatedClient::new(roots)
+            })
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_cert_resolver(Arc::new(resolver));
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    let tls_acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+
+    tcp_listener.set_nonblocking(true)?;
+    let tcp_listener = tokio::net::TcpListener::from_std(tcp_listener)?;
+
+    let (sender, receiver) = mpsc::channel(64);
+
+    tokio::spawn(async move {
+        loop {
+            let tcp_stream = tokio::select! {
+                accepted = tcp_listener.accept() => match accepted {
+                    Ok((tcp_stream, _)) => tcp_stream,
+                    Err(error) => {
+                        tracing::warn!(%error, "Failed to accept connection");
+                        continue;
+                    }
+                },
+                () = sender.closed() => return,
+            };
+
+            let tls_acceptor = tls_acceptor.clone();
+            let sender = sender.clone();
+
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, tls_acceptor.accept(tcp_stream)).await
+                {
+                    Ok(Ok(tls_stream)) => {
+                        let _ = sender.send(tls_stream).await;
+                    }
+                    Ok(Err(error)) => tracing::debug!(%error, "TLS handshake failed"),
+                    Err(_) => tracing::debug!("TLS handshake timed out"),
+                }
+            });
+        }
+    });
+
+    Ok(Incoming(receiver))
+}
+
+impl hyper::server::accept::Accept for Incoming {
+    type Conn = TlsStream<TcpStream>;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        self.0.poll_recv(cx).map(|tls_stream| tls_stream.map(Ok))
+    }
+}
+
+impl axum::extract::connect_info::Connected<&TlsStream<TcpStream>> for ClientCertificate {
+    fn connect_info(target: &TlsStream<TcpStream>) -> ClientCertificate {
+        let (_, server_connection) = target.get_ref();
+
+        ClientCertificate {
+            subject: server_connection
+                .peer_certificates()
+                .and_then(<[_]>::first)
+                .and_then(|certificate| common_name(&certificate.0)),
+        }
+    }
+}
+
+impl Resolver {
+    fn new(certificate: &str, key: &str) -> crate::Result<Resolver> {
+        let certificate = PathBuf::from(certificate);
+        let key = PathBuf::from(key);
+        let loaded = Loaded::load(&certificate, &key)?;
+
+        Ok(Resolver {
+            certificate,
+            key,
+            loaded: Mutex::new(loaded),
+        })
+    }
+}
+
+impl rustls::server::ResolvesServerCert for Resolver {
+    fn resolve(
+        &self,
+        _: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        let mut loaded = self.loaded.lock().unwrap();
+
+        if modified(&self.certificate, &self.key) != loaded.modified {
+            match Loaded::load(&self.certificate, &self.key) {
+                Ok(reloaded) => {
+                    tracing::info!(certificate = %self.certificate.display(), "Certificate reloaded");
+                    *loaded = reloaded;
+                }
+                // the files may be replaced one at a time, the previous certificate is served
+                // until both are consistent.
+                Err(error) => tracing::warn!(%error, "Failed to reload certificate"),
+            }
+        }
+
+        Some(loaded.certified_key.clone())
+    }
+}
+
+impl Loaded {
+    fn load(certificate: &std::path::Path, key_path: &std::path::Path) -> crate::Result<Loaded> {
+        let modified = modified(certificate, key_path);
+
+        let chain = certificates(certificate)?;
+
+        let key =
+            rustls_pemfile::read_all(&mut std::io::BufReader::new(std::fs::File::open(key_path)?))?
+                .into_iter()
+                .find_map(|item| match item {
+                    rustls_pemfile::Item::ECKey(key)
+                    | rustls_pemfile::Item::PKCS8Key(key)
+                    | rustls_pemfile::Item::RSAKey(key) => Some(rustls::PrivateKey(key)),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("No private key in {}", key_path.display()))?;
+
+        let signing_key = rustls::sign::any_supported_type(&key)?;
+
+        // a certificate replaced before its key would fail every handshake.
+        if !keys_match(&chain[0], signing_key.as_ref()) {
+            return Err(format!(
+                "Private key {} does not match certificate {}",
+                key_path.display(),
+                certificate.display()
+            )
+            .into());
+        }
+
+        Ok(Loaded {
+            modified,
+            certified_key: Arc::new(rustls::sign::CertifiedKey::new(chain, signing_key)),
+        })
+    }
+}
+
+/// Checks the signing key is the private key of the `certificate`, by verifying a signature made
+/// with it against the public key of the certificate.
+fn keys_match(
+    certificate: &rustls::Certificate,
+    signing_key: &dyn rustls::sign::SigningKey,
+) -> bool {
+    use rustls::SignatureScheme;
+
+    const MESSAGE: &[u8] = b"container-registry-gateway certificate and key match";
+
+    let Some(signer) = signing_key.choose_scheme(&[
+        SignatureScheme::ECDSA_NISTP256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384,
+        SignatureScheme::ED25519,
+        SignatureScheme::RSA_PSS_SHA256,
+    ]) else {
+        return false;
+    };
+
+    let algorithm = match signer.scheme() {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => &webpki::ECDSA_P256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384 => &webpki::ECDSA_P384_SHA384,
+        SignatureScheme::ED25519 => &webpki::ED25519,
+        SignatureScheme::RSA_PSS_SHA256 => &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+        _ => return false,
+    };
+
+    let Ok(signature) = signer.sign(MESSAGE) else {
+        return false;
+    };
+
+    webpki::EndEntityCert::try_from(certificate.0.as_slice()).is_ok_and(|certificate| {
+        certificate
+            .verify_signature(algorithm, MESSAGE, &signature)
+            .is_ok()
+    })
+}
+
+/// Reads the PEM encoded certificates of the file at `path`.
+fn certificates(path: &std::path::Path) -> crate::Result<Vec<rustls::Certificate>> {
+    let certificates =
+        rustls_pemfile::certs(&mut std::io::BufReader::new(std::fs::File::open(path)?))?;
+
+    if certificates.is_empty() {
+        return Err(format!("No certificate in {}", path.display()).into());
+    }
+
+    Ok(certificates.into_iter().map(rustls::Certificate).collect())
+}
+
+fn modified(
+    certificate: &std::path::Path,
+    key: &std::path::Path,
+) -> (Option<SystemTime>, Option<SystemTime>) {
+    let modified = |path| {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    (modified(certificate), modified(key))
+}
+
+/// Returns the common name of the subject of the DER encoded certificate.
+fn common_name(der: &[u8]) -> Option<String> {
+    let certificate = x509_cert::Certificate::from_der(der).ok()?;
+
+    certificate
+        .tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|relative_distinguished_name| relative_distinguished_name.0.iter())
+        .find(|attribute| attribute.oid == COMMON_NAME)
+        .and_then(|attribute| {
+            attribute
+                .value
+                .decode_as::<Utf8StringRef<'_>>()
+                .map(|value| value.to_string())
+                .or_else(|_| {
+                    attribute
+                        .value
+                        .decode_as::<PrintableStringRef<'_>>()
+                        .map(|value| value.to_string())
+                })
+                .ok()
+        })
+}
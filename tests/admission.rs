@@ -0,0 +1,120 @@
+mod common;
+
+use common::{parse_body, start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use std::net::SocketAddr;
+
+const INDEX_DIGEST: &str =
+    "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+const PLATFORM_DIGEST: &str =
+    "sha256:2222222222222222222222222222222222222222222222222222222222222222";
+
+async fn start_registry() -> SocketAddr {
+    start_mock(|parts, _| {
+        let (media_type, digest, body) = match parts.uri.path() {
+            "/v2/app/manifests/latest" => (
+                "application/vnd.oci.image.index.v1+json",
+                INDEX_DIGEST,
+                serde_json::json!({
+                    "schemaVersion": 2,
+                    "mediaType": "application/vnd.oci.image.index.v1+json",
+                    "manifests": [{
+                        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                        "digest": PLATFORM_DIGEST,
+                        "size": 1,
+                        "platform": { "architecture": "amd64", "os": "linux" },
+                    }],
+                }),
+            ),
+            path if path.ends_with(PLATFORM_DIGEST) => (
+                "application/vnd.oci.image.manifest.v1+json",
+                PLATFORM_DIGEST,
+                serde_json::json!({ "schemaVersion": 2 }),
+            ),
+            _ => {
+                return hyper::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(hyper::Body::empty())
+                    .unwrap()
+            }
+        };
+
+        hyper::Response::builder()
+            .header(hyper::header::CONTENT_TYPE, media_type)
+            .header("docker-content-digest", digest)
+            .body(serde_json::to_vec(&body).unwrap().into())
+            .unwrap()
+    })
+    .await
+}
+
+async fn get(socket_addr: SocketAddr, path: &str) -> hyper::Response<hyper::Body> {
+    Client::new()
+        .get(format!("http://{socket_addr}{path}").parse().unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn v2_platform_manifest_inherits_admission_of_index() {
+    let registry = start_registry().await;
+    let snyk = start_snyk(|name| (name == "app:latest").then_some([0, 0, 0, 0])).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+    ])
+    .await;
+
+    let response = get(socket_addr, "/v2/app/manifests/latest").await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = get(socket_addr, &format!("/v2/app/manifests/{PLATFORM_DIGEST}")).await;
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn v2_platform_manifest_without_lineage_is_not_monitored() {
+    let registry = start_registry().await;
+    let snyk = start_snyk(|name| (name == "app:latest").then_some([0, 0, 0, 0])).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+    ])
+    .await;
+
+    let response = get(socket_addr, &format!("/v2/app/manifests/{PLATFORM_DIGEST}")).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let body = parse_body(response).await;
+    assert_eq!(
+        "Image not monitored for vulnerabilities",
+        body.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_platform_manifest_is_admitted_individually_in_platform_mode() {
+    let registry = start_registry().await;
+    let snyk = start_snyk(|name| (name == "app:latest").then_some([1, 0, 0, 0])).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("admission.index", "platform"),
+    ])
+    .await;
+
+    let response = get(socket_addr, "/v2/app/manifests/latest").await;
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = get(socket_addr, &format!("/v2/app/manifests/{PLATFORM_DIGEST}")).await;
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let body = parse_body(response).await;
+    assert_eq!(
+        "Image exceeded vulnerability threshold critical",
+        body.errors[0].message
+    );
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use base64::Engine as _;
+use common::{parse_body, start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use p256::{
+    ecdsa::{signature::Signer as _, Signature, SigningKey},
+    pkcs8::{EncodePublicKey as _, LineEnding},
+};
+use sha2::Digest as _;
+use std::{collections::HashMap, net::SocketAddr};
+
+const BUILDER_ID: &str = "https://github.com/slsa-framework/slsa-github-generator/.github/workflows/generator_container_slsa3.yml@refs/tags/v1.4.0";
+
+fn digest(content: &[u8]) -> String {
+    format!("sha256:{:x}", sha2::Sha256::digest(content))
+}
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[1; 32].into()).unwrap()
+}
+
+fn sign(payload: &[u8]) -> String {
+    let signature: Signature = signing_key().sign(payload);
+
+    base64::engine::general_purpose::STANDARD.encode(signature.to_der())
+}
+
+fn image_manifest(layers: &serde_json::Value) -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(b"{}"),
+            "size": 2,
+        },
+        "layers": layers,
+    }))
+    .unwrap()
+}
+
+/// Starts a mock registry serving a signed `app:latest`, attested by the `predicates`.
+async fn start_registry(predicates: &[(&str, serde_json::Value)]) -> SocketAddr {
+    let manifest = image_manifest(&serde_json::json!([]));
+    let manifest_digest = digest(&manifest);
+    let (algorithm, encoded) = manifest_digest.split_once(':').unwrap();
+
+    let mut manifests = HashMap::new();
+    let mut blobs = HashMap::new();
+
+    manifests.insert("latest".to_string(), manifest.clone());
+    manifests.insert(manifest_digest.clone(), manifest);
+
+    let payload = serde_json::to_vec(&serde_json::json!({
+        "critical": {
+            "identity": { "docker-reference": "registry.local/app" },
+            "image": { "docker-manifest-digest": manifest_digest },
+            "type": "cosign container image signature",
+        },
+        "optional": null,
+    }))
+    .unwrap();
+
+    manifests.insert(
+        format!("{algorithm}-{encoded}.sig"),
+        image_manifest(&serde_json::json!([{
+            "mediaType": "application/vnd.dev.cosign.simplesigning.v1+json",
+            "digest": digest(&payload),
+            "size": payload.len(),
+            "annotations": { "dev.cosignproject.cosign/signature": sign(&payload) },
+        }])),
+    );
+    blobs.insert(digest(&payload), payload);
+
+    let mut layers = Vec::new();
+
+    for (predicate_type, predicate) in predicates {
+        let statement = serde_json::to_vec(&serde_json::json!({
+            "_type": "https://in-toto.io/Statement/v0.1",
+            "predicateType": predicate_type,
+            "subject": [{ "name": "registry.local/app", "digest": { algorithm: encoded } }],
+            "predicate": predicate,
+        }))
+        .unwrap();
+
+        let payload_type = "application/vnd.in-toto+json";
+        let pae = [
+            format!(
+                "DSSEv1 {} {payload_type} {} ",
+                payload_type.len(),
+                statement.len()
+            )
+            .as_bytes(),
+            &statement,
+        ]
+        .concat();
+
+        let envelope = serde_json::to_vec(&serde_json::json!({
+            "payloadType": payload_type,
+            "payload": base64::engine::general_purpose::STANDARD.encode(&statement),
+            "signatures": [{ "keyid": "", "sig": sign(&pae) }],
+        }))
+        .unwrap();
+
+        layers.push(serde_json::json!({
+            "mediaType": "application/vnd.dsse.envelope.v1+json",
+            "digest": digest(&envelope),
+            "size": envelope.len(),
+            "annotations": { "predicateType": predicate_type },
+        }));
+        blobs.insert(digest(&envelope), envelope);
+    }
+
+    manifests.insert(
+        format!("{algorithm}-{encoded}.att"),
+        image_manifest(&serde_json::Value::Array(layers)),
+    );
+
+    start_mock(move |parts, _| {
+        let path = parts.uri.path();
+
+        let found = path
+            .strip_prefix("/v2/app/manifests/")
+            .and_then(|reference| manifests.get(reference))
+            .map(|manifest| (manifest, "application/vnd.oci.image.manifest.v1+json"))
+            .or_else(|| {
+                path.strip_prefix("/v2/app/blobs/")
+                    .and_then(|digest| blobs.get(digest))
+                    .map(|blob| (blob, "application/octet-stream"))
+            });
+
+        match found {
+            Some((body, media_type)) => hyper::Response::builder()
+                .header(hyper::header::CONTENT_TYPE, media_type)
+                .header("docker-content-digest", digest(body))
+                .body(body.clone().into())
+                .unwrap(),
+            None => hyper::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        }
+    })
+    .await
+}
+
+async fn pull(registry: SocketAddr) -> hyper::Response<hyper::Body> {
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let public_key = signing_key()
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .unwrap();
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("signature.public_keys[0]", &public_key),
+        ("attestation.require_sbom", "true"),
+        ("attestation.builder_ids[0]", BUILDER_ID),
+        (
+            "attestation.source_repositories[0]",
+            "https://github.com/example/app",
+        ),
+        ("attestation.branches[0]", "main"),
+    ])
+    .await;
+
+    Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/app/manifests/latest")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+fn provenance(builder_id: &str) -> (&'static str, serde_json::Value) {
+    (
+        "https://slsa.dev/provenance/v0.2",
+        serde_json::json!({
+            "builder": { "id": builder_id },
+            "buildType": "https://github.com/slsa-framework/slsa-github-generator/container@v1",
+            "invocation": {
+                "configSource": {
+                    "uri": "git+https://github.com/example/app@refs/heads/main",
+                    "entryPoint": ".github/workflows/release.yml",
+                },
+            },
+        }),
+    )
+}
+
+fn sbom() -> (&'static str, serde_json::Value) {
+    (
+        "https://spdx.dev/Document",
+        serde_json::json!({ "spdxVersion": "SPDX-2.3", "packages": [] }),
+    )
+}
+
+#[tokio::test]
+async fn v2_manifest_with_trusted_provenance_and_sbom_is_admitted() {
+    let registry = start_registry(&[provenance(BUILDER_ID), sbom()]).await;
+
+    let response = pull(registry).await;
+
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn v2_manifest_with_untrusted_builder_is_denied() {
+    let registry = start_registry(&[provenance("https://example.com/builder"), sbom()]).await;
+
+    let response = pull(registry).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image provenance attestation does not satisfy policy",
+        parse_body(response).await.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_manifest_without_sbom_is_denied() {
+    let registry = start_registry(&[provenance(BUILDER_ID)]).await;
+
+    let response = pull(registry).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image SBOM attestation not found",
+        parse_body(response).await.errors[0].message
+    );
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use common::{start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+async fn issue(
+    socket_addr: SocketAddr,
+    admin_token: &str,
+    repository: &str,
+) -> hyper::Response<hyper::Body> {
+    Client::new()
+        .request(
+            hyper::Request::post(format!("http://{socket_addr}/break-glass"))
+                .header(
+                    hyper::header::AUTHORIZATION,
+                    format!("Bearer {admin_token}"),
+                )
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(
+                    serde_json::to_vec(&serde_json::json!({
+                        "repository": repository,
+                        "digest": DIGEST,
+                        "ttl": 600,
+                        "reason": "INC-1234",
+                    }))
+                    .unwrap()
+                    .into(),
+                )
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn pull(socket_addr: SocketAddr, token: Option<&str>) -> StatusCode {
+    let mut request = hyper::Request::get(format!("http://{socket_addr}/v2/app/manifests/latest"));
+
+    if let Some(token) = token {
+        request = request.header("x-break-glass", token);
+    }
+
+    Client::new()
+        .request(request.body(hyper::Body::empty()).unwrap())
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn v2_pull_with_break_glass_token_bypasses_admission() {
+    let forwarded = Arc::new(Mutex::new(Vec::new()));
+    let registry = start_mock({
+        let forwarded = forwarded.clone();
+        move |parts, _| {
+            forwarded
+                .lock()
+                .unwrap()
+                .extend(parts.headers.get("x-break-glass").cloned());
+
+            hyper::Response::builder()
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    "application/vnd.oci.image.manifest.v1+json",
+                )
+                .header("docker-content-digest", DIGEST)
+                .body(hyper::Body::from("{}"))
+                .unwrap()
+        }
+    })
+    .await;
+    let snyk = start_snyk(|_| None).await;
+
+    let received = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+    let sink = start_mock({
+        let received = received.clone();
+        move |_, body| {
+            received
+                .lock()
+                .unwrap()
+                .push(serde_json::from_slice(&body).unwrap());
+            hyper::Response::new(hyper::Body::empty())
+        }
+    })
+    .await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("break_glass.secret", "secret"),
+        ("break_glass.admin_token", "admin"),
+        ("notification.targets[0].url", &format!("http://{sink}")),
+        ("notification.targets[0].events[0]", "break_glass"),
+    ])
+    .await;
+
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        issue(socket_addr, "guess", "app").await.status()
+    );
+
+    let body = hyper::body::to_bytes(issue(socket_addr, "admin", "app").await)
+        .await
+        .unwrap();
+    let issued: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let token = issued["token"].as_str().unwrap();
+
+    assert_eq!("INC-1234", issued["reason"]);
+    assert_eq!(StatusCode::FORBIDDEN, pull(socket_addr, None).await);
+    assert_eq!(StatusCode::OK, pull(socket_addr, Some(token)).await);
+    assert!(forwarded.lock().unwrap().is_empty());
+
+    let body = hyper::body::to_bytes(issue(socket_addr, "admin", "other").await)
+        .await
+        .unwrap();
+    let issued: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        pull(socket_addr, issued["token"].as_str()).await
+    );
+
+    // the claims are encoded from a JSON object, so the token starts with `eyJ`.
+    let forged = token.replacen("eyJ", "fyJ", 1);
+
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        pull(socket_addr, Some(&forged)).await
+    );
+
+    for _ in 0..50 {
+        if received.lock().unwrap().len() >= 3 {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let received = received.lock().unwrap();
+
+    assert_eq!(3, received.len());
+    assert!(received.iter().all(|event| event["event"] == "break_glass"));
+    assert!(received
+        .iter()
+        .any(|event| event["message"] == "Admission bypassed with break-glass token: INC-1234"));
+}
+
+#[tokio::test]
+async fn v2_pull_with_break_glass_token_in_username_bypasses_admission() {
+    use base64::Engine as _;
+
+    let authorizations = Arc::new(Mutex::new(Vec::new()));
+    let registry = start_mock({
+        let authorizations = authorizations.clone();
+        move |parts, _| {
+            authorizations
+                .lock()
+                .unwrap()
+                .push(parts.headers.get(hyper::header::AUTHORIZATION).cloned());
+
+            hyper::Response::builder()
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    "application/vnd.oci.image.manifest.v1+json",
+                )
+                .header("docker-content-digest", DIGEST)
+                .body(hyper::Body::from("{}"))
+                .unwrap()
+        }
+    })
+    .await;
+    let snyk = start_snyk(|_| None).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("break_glass.secret", "secret"),
+        ("break_glass.admin_token", "admin"),
+    ])
+    .await;
+
+    let body = hyper::body::to_bytes(issue(socket_addr, "admin", "app").await)
+        .await
+        .unwrap();
+    let issued: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let token = issued["token"].as_str().unwrap();
+
+    let pull = |username: String| async move {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{username}:password"));
+
+        Client::new()
+            .request(
+                hyper::Request::get(format!("http://{socket_addr}/v2/app/manifests/latest"))
+                    .header(hyper::header::AUTHORIZATION, format!("Basic {credentials}"))
+                    .body(hyper::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(StatusCode::FORBIDDEN, pull("oncall".to_string()).await);
+    assert_eq!(
+        StatusCode::OK,
+        pull(format!("oncall+break-glass={token}")).await
+    );
+    assert_eq!(StatusCode::OK, pull(format!("+break-glass={token}")).await);
+
+    // the upstream authorizes the pulls with the credentials stripped of the token.
+    let oncall = format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode("oncall:password")
+    );
+    let authorizations = authorizations.lock().unwrap();
+
+    assert!(authorizations.iter().all(|authorization| {
+        authorization
+            .as_ref()
+            .is_none_or(|authorization| authorization == oncall.as_str())
+    }));
+    assert_eq!(Some(&None), authorizations.last());
+}
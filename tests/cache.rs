@@ -0,0 +1,306 @@
+mod common;
+
+use common::{start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use sha2::Digest as _;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+const BLOB: &str = "hello world";
+const BLOB_DIGEST: &str = "sha256:b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+#[tokio::test]
+async fn v2_blob_is_served_from_cache() {
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let upstream = start_mock({
+        let requests = requests.clone();
+        move |parts, _| {
+            if parts.method == hyper::Method::GET {
+                requests.fetch_add(1, Ordering::SeqCst);
+            }
+
+            hyper::Response::new(BLOB.into())
+        }
+    })
+    .await;
+
+    let directory = std::env::temp_dir().join(format!("crg-cache-{}", upstream.port()));
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{upstream}")),
+        ("cache.directory", directory.to_str().unwrap()),
+        ("cache.max_size", "1024"),
+    ])
+    .await;
+
+    for _ in 0..2 {
+        let response = Client::new()
+            .get(
+                format!("http://{socket_addr}/v2/library/nginx/blobs/{BLOB_DIGEST}")
+                    .parse()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(BLOB_DIGEST, response.headers()["docker-content-digest"]);
+
+        let body = hyper::body::to_bytes(response).await.unwrap();
+
+        assert_eq!(BLOB, body);
+    }
+
+    assert_eq!(1, requests.load(Ordering::SeqCst));
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn v2_blob_with_mismatched_digest_is_not_cached() {
+    let upstream = start_mock(|_, _| hyper::Response::new("tampered".into())).await;
+
+    let directory = std::env::temp_dir().join(format!("crg-cache-{}", upstream.port()));
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{upstream}")),
+        ("cache.directory", directory.to_str().unwrap()),
+        ("cache.max_size", "1024"),
+    ])
+    .await;
+
+    let response = Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/library/nginx/blobs/{BLOB_DIGEST}")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(hyper::body::to_bytes(response).await.is_err());
+    assert!(!directory
+        .join("sha256")
+        .join(BLOB_DIGEST.trim_start_matches("sha256:"))
+        .exists());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn v2_cached_private_blob_requires_authorization() {
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let upstream = start_mock({
+        let requests = requests.clone();
+        move |parts, _| {
+            if !parts.headers.contains_key(hyper::header::AUTHORIZATION) {
+                return hyper::Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .header(hyper::header::WWW_AUTHENTICATE, r#"Basic realm="registry""#)
+                    .body(hyper::Body::empty())
+                    .unwrap();
+            }
+
+            if parts.method == hyper::Method::GET {
+                requests.fetch_add(1, Ordering::SeqCst);
+            }
+
+            hyper::Response::new(BLOB.into())
+        }
+    })
+    .await;
+
+    let directory = std::env::temp_dir().join(format!("crg-cache-{}", upstream.port()));
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{upstream}")),
+        ("cache.directory", directory.to_str().unwrap()),
+        ("cache.max_size", "1024"),
+    ])
+    .await;
+
+    let get = |authorization: Option<&'static str>| async move {
+        let mut request = hyper::Request::get(format!(
+            "http://{socket_addr}/v2/library/nginx/blobs/{BLOB_DIGEST}"
+        ));
+
+        if let Some(authorization) = authorization {
+            request = request.header(hyper::header::AUTHORIZATION, authorization);
+        }
+
+        Client::new()
+            .request(request.body(hyper::Body::empty()).unwrap())
+            .await
+            .unwrap()
+    };
+
+    // admin:secret
+    let response = get(Some("Basic YWRtaW46c2VjcmV0")).await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(BLOB, hyper::body::to_bytes(response).await.unwrap());
+
+    let response = get(None).await;
+
+    assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    assert!(response
+        .headers()
+        .contains_key(hyper::header::WWW_AUTHENTICATE));
+
+    let response = get(Some("Basic YWRtaW46c2VjcmV0")).await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(BLOB, hyper::body::to_bytes(response).await.unwrap());
+    assert_eq!(1, requests.load(Ordering::SeqCst));
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn v2_manifest_tag_is_served_from_cache_until_ttl_expires() {
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let upstream = start_mock({
+        let requests = requests.clone();
+        move |parts, _| {
+            let mut response = hyper::Response::builder().header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            );
+
+            // each fetch of the tag returns a new manifest, as if the tag was pushed again.
+            let body = if parts.method == hyper::Method::GET {
+                let count = requests.fetch_add(1, Ordering::SeqCst) + 1;
+                format!(r#"{{"schemaVersion":2,"annotations":{{"push":"{count}"}}}}"#)
+            } else {
+                response = response.header("docker-content-digest", BLOB_DIGEST);
+                String::new()
+            };
+
+            response.body(body.into()).unwrap()
+        }
+    })
+    .await;
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let directory = std::env::temp_dir().join(format!("crg-cache-{}", upstream.port()));
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{upstream}")),
+        ("cache.directory", directory.to_str().unwrap()),
+        ("cache.max_size", "1024"),
+        ("cache.manifest_ttl", "1"),
+        ("snyk.base_address", &format!("http://{snyk}")),
+    ])
+    .await;
+
+    let get = || async move {
+        let response = Client::new()
+            .get(
+                format!("http://{socket_addr}/v2/library/nginx/manifests/latest")
+                    .parse()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+
+        hyper::body::to_bytes(response).await.unwrap()
+    };
+
+    let fetched = get().await;
+
+    assert_eq!(fetched, get().await);
+    assert_eq!(1, requests.load(Ordering::SeqCst));
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    assert_ne!(fetched, get().await);
+    assert_eq!(2, requests.load(Ordering::SeqCst));
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn v2_least_recently_used_blob_is_evicted() {
+    let blobs = ["hello world", "hello there", "hello again"]
+        .map(|blob| (format!("sha256:{:x}", sha2::Sha256::digest(blob)), blob));
+    let requests = Arc::new(Mutex::new(HashMap::<String, usize>::new()));
+
+    let upstream = start_mock({
+        let blobs = blobs.clone();
+        let requests = requests.clone();
+        move |parts, _| {
+            let Some((digest, blob)) = blobs
+                .iter()
+                .find(|(digest, _)| parts.uri.path().ends_with(digest.as_str()))
+            else {
+                return hyper::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(hyper::Body::empty())
+                    .unwrap();
+            };
+
+            if parts.method == hyper::Method::GET {
+                *requests.lock().unwrap().entry(digest.clone()).or_default() += 1;
+            }
+
+            hyper::Response::new((*blob).into())
+        }
+    })
+    .await;
+
+    let directory = std::env::temp_dir().join(format!("crg-cache-{}", upstream.port()));
+
+    // the cache holds two of the blobs.
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{upstream}")),
+        ("cache.directory", directory.to_str().unwrap()),
+        ("cache.max_size", "25"),
+    ])
+    .await;
+
+    let get = |digest: String| async move {
+        let response = Client::new()
+            .get(
+                format!("http://{socket_addr}/v2/library/nginx/blobs/{digest}")
+                    .parse()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(StatusCode::OK, response.status());
+
+        hyper::body::to_bytes(response).await.unwrap();
+    };
+
+    let [(first, _), (second, _), (third, _)] = &blobs;
+
+    get(first.clone()).await;
+    get(second.clone()).await;
+    get(first.clone()).await;
+    get(third.clone()).await;
+
+    // the second blob was the least recently used when the third was stored.
+    get(first.clone()).await;
+    get(second.clone()).await;
+
+    let requests = requests.lock().unwrap();
+
+    assert_eq!(1, requests[first]);
+    assert_eq!(2, requests[second]);
+    assert_eq!(1, requests[third]);
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
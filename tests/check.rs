@@ -0,0 +1,189 @@
+mod common;
+
+use common::{start_mock, start_snyk};
+use container_registry_gateway::{check, configuration};
+use std::net::SocketAddr;
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+/// Starts a mock registry and a mock Snyk API, which reports critical vulnerabilities in
+/// `app:vulnerable` and a low vulnerability in `app` pinned by digest.
+async fn start() -> (SocketAddr, SocketAddr) {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .header("docker-content-digest", DIGEST)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let snyk = start_snyk(|name| match name {
+        "library/app:vulnerable" => Some([1, 2, 3, 4]),
+        "app:v1" | "library/app:v1" => Some([0, 0, 0, 0]),
+        name if name == format!("app:{DIGEST}") => Some([0, 0, 0, 1]),
+        _ => None,
+    })
+    .await;
+
+    (registry, snyk)
+}
+
+fn load_configuration(registry: SocketAddr, snyk: SocketAddr) -> configuration::Configuration {
+    configuration::load(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("oci.registry", "docker.io"),
+        ("kubernetes.gateway", "gateway.example.com"),
+        ("snyk.api_key", "key"),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+    ])
+    .unwrap()
+}
+
+#[tokio::test]
+async fn check_reports_verdict_with_vulnerabilities() {
+    let (registry, snyk) = start().await;
+
+    let configuration = load_configuration(registry, snyk);
+
+    let verdict = check::run(configuration.clone(), "gateway.example.com/app:v1")
+        .await
+        .unwrap();
+
+    assert!(verdict.admitted);
+    assert_eq!(Some(DIGEST), verdict.digest.as_deref());
+
+    let verdict = check::run(configuration, "app:vulnerable").await.unwrap();
+
+    assert!(!verdict.admitted);
+    assert_eq!(
+        serde_json::json!({
+            "image": "app:vulnerable",
+            "digest": DIGEST,
+            "admitted": false,
+            "message": "Image exceeded vulnerability threshold critical",
+            "vulnerabilities": { "critical": 1, "high": 2, "medium": 3, "low": 4 },
+        }),
+        serde_json::to_value(&verdict).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn check_of_image_pinned_by_digest_reports_its_vulnerabilities() {
+    let (registry, snyk) = start().await;
+
+    let image = format!("gateway.example.com/app@{DIGEST}");
+    let verdict = check::run(load_configuration(registry, snyk), &image)
+        .await
+        .unwrap();
+
+    assert!(!verdict.admitted);
+    assert_eq!(
+        "Image exceeded vulnerability threshold low",
+        verdict.message.unwrap()
+    );
+    assert_eq!(1, verdict.vulnerabilities.unwrap().low);
+}
+
+#[tokio::test]
+async fn check_of_image_of_unserved_registry_fails() {
+    let (registry, snyk) = start().await;
+
+    let error = check::run(load_configuration(registry, snyk), "quay.io/app:v1")
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!("No upstream serves the registry quay.io", error.to_string());
+}
+
+#[tokio::test]
+async fn check_of_image_of_mirror_upstream_queries_its_registry() {
+    const GHCR_DIGEST: &str =
+        "sha256:2222222222222222222222222222222222222222222222222222222222222222";
+
+    let (registry, _) = start().await;
+    let ghcr = start_mock(|parts, _| {
+        let status = if parts.uri.path() == "/v2/org/app/manifests/1" {
+            hyper::StatusCode::OK
+        } else {
+            hyper::StatusCode::NOT_FOUND
+        };
+
+        hyper::Response::builder()
+            .status(status)
+            .header("docker-content-digest", GHCR_DIGEST)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+    let snyk = start_snyk(|name| (name == "org/app:1").then_some([0, 0, 0, 2])).await;
+
+    let configuration = configuration::load(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("oci.registry", "docker.io"),
+        ("oci.mirror", "true"),
+        ("oci.upstreams[0].registry", "ghcr.io"),
+        ("oci.upstreams[0].base_address", &format!("http://{ghcr}")),
+        ("snyk.api_key", "key"),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+    ])
+    .unwrap();
+
+    let verdict = check::run(configuration, "ghcr.io/org/app:1")
+        .await
+        .unwrap();
+
+    assert_eq!(Some(GHCR_DIGEST), verdict.digest.as_deref());
+    assert_eq!(
+        "Image exceeded vulnerability threshold low",
+        verdict.message.unwrap()
+    );
+    assert_eq!(2, verdict.vulnerabilities.unwrap().low);
+}
+
+#[tokio::test]
+async fn check_binary_exits_with_failure_on_denied_image() {
+    let (registry, snyk) = start().await;
+
+    let check = |image: &str| {
+        tokio::process::Command::new(env!("CARGO_BIN_EXE_container-registry-gateway-check"))
+            .args(["--format", "json", image])
+            .env("CONTAINER_REGISTRY_GATEWAY__HTTP_SERVER__HOST", "127.0.0.1")
+            .env(
+                "CONTAINER_REGISTRY_GATEWAY__OCI__BASE_ADDRESS",
+                format!("http://{registry}"),
+            )
+            .env("CONTAINER_REGISTRY_GATEWAY__OCI__REGISTRY", "docker.io")
+            .env("CONTAINER_REGISTRY_GATEWAY__SNYK__API_KEY", "key")
+            .env(
+                "CONTAINER_REGISTRY_GATEWAY__SNYK__BASE_ADDRESS",
+                format!("http://{snyk}"),
+            )
+            .env(
+                "CONTAINER_REGISTRY_GATEWAY__SNYK__INTEGRATION_ID",
+                "integration",
+            )
+            .env(
+                "CONTAINER_REGISTRY_GATEWAY__SNYK__ORGANIZATION_ID",
+                "organization",
+            )
+            .output()
+    };
+
+    let output = check("app:v1").await.unwrap();
+
+    assert_eq!(Some(0), output.status.code());
+
+    let output = check("app:unknown").await.unwrap();
+    let verdict: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "Image not monitored for vulnerabilities",
+        verdict["message"]
+    );
+}
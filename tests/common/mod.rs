@@ -0,0 +1,168 @@
+#![allow(dead_code)]
+
+use container_registry_gateway::{configuration, oci::Response, server, shutdown};
+use hyper::{
+    body::{Buf as _, Bytes},
+    http::request::Parts,
+    service::{make_service_fn, service_fn},
+};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tokio::sync::mpsc::Sender;
+
+pub async fn start_server() -> SocketAddr {
+    start_server_with(&[("oci.base_address", "https://registry-1.docker.io")]).await
+}
+
+pub async fn start_server_with(overrides: &[(&str, &str)]) -> SocketAddr {
+    start_server_with_configuration(load_configuration(overrides)).await
+}
+
+pub async fn start_server_with_configuration(
+    configuration: configuration::Configuration,
+) -> SocketAddr {
+    let tcp_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let socket_addr = tcp_listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        server::run(
+            tcp_listener.into_std().unwrap(),
+            shutdown::recv(),
+            configuration,
+        )
+        .await
+    });
+
+    socket_addr
+}
+
+/// Starts a server applying the configurations sent through the returned sender.
+pub async fn start_server_with_reload(
+    overrides: &[(&str, &str)],
+) -> (SocketAddr, Sender<configuration::Configuration>) {
+    let tcp_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    let configuration = load_configuration(overrides);
+
+    let socket_addr = tcp_listener.local_addr().unwrap();
+
+    let (reloads_sender, reloads) = tokio::sync::mpsc::channel(1);
+
+    tokio::spawn(async move {
+        server::run_with_reload(
+            tcp_listener.into_std().unwrap(),
+            shutdown::recv(),
+            configuration,
+            reloads,
+        )
+        .await
+    });
+
+    (socket_addr, reloads_sender)
+}
+
+/// Break-glass settings, authorizing admin requests with the token `admin`.
+pub const ADMIN: [(&str, &str); 2] = [
+    ("break_glass.secret", "secret"),
+    ("break_glass.admin_token", "admin"),
+];
+
+/// Gets the `path` with the admin token of the `ADMIN` settings.
+pub async fn get_as_admin(socket_addr: SocketAddr, path: &str) -> hyper::Response<hyper::Body> {
+    hyper::Client::new()
+        .request(
+            hyper::Request::get(format!("http://{socket_addr}{path}"))
+                .header(hyper::header::AUTHORIZATION, "Bearer admin")
+                .body(hyper::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+/// Loads the configuration, with placeholder Snyk settings.
+pub fn load_configuration(overrides: &[(&str, &str)]) -> configuration::Configuration {
+    let overrides = [
+        ("snyk.api_key", "key"),
+        ("snyk.base_address", "http://127.0.0.1:9"),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+    ]
+    .iter()
+    .chain(overrides)
+    .copied()
+    .collect::<Vec<_>>();
+
+    configuration::load(&overrides).unwrap()
+}
+
+/// Starts a mock server responding to every request with `handler`.
+pub async fn start_mock<F>(handler: F) -> SocketAddr
+where
+    F: Fn(Parts, Bytes) -> hyper::Response<hyper::Body> + Send + Sync + 'static,
+{
+    let handler = Arc::new(handler);
+
+    let make_service = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<hyper::Body>| {
+                let handler = handler.clone();
+                async move {
+                    let (parts, body) = request.into_parts();
+                    let body = hyper::body::to_bytes(body).await.unwrap();
+                    Ok::<_, Infallible>(handler(parts, body))
+                }
+            }))
+        }
+    });
+
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+
+    let socket_addr = server.local_addr();
+
+    tokio::spawn(server);
+
+    socket_addr
+}
+
+pub async fn parse_body(response: hyper::Response<hyper::Body>) -> Response {
+    let buffer = hyper::body::aggregate(response).await.unwrap();
+
+    serde_json::from_reader(buffer.reader()).unwrap()
+}
+
+/// Starts a mock Snyk API, returning the issue counts of `[critical, high, medium, low]`
+/// severity for the monitored projects.
+pub async fn start_snyk<F>(projects: F) -> SocketAddr
+where
+    F: Fn(&str) -> Option<[u32; 4]> + Send + Sync + 'static,
+{
+    start_mock(move |_, body| {
+        let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        let name = body["filters"]["name"].as_str().unwrap_or_default();
+
+        let projects = projects(name)
+            .map(|[critical, high, medium, low]| {
+                serde_json::json!({
+                    "name": name,
+                    "attributes": { "criticality": [] },
+                    "issueCountsBySeverity": {
+                        "critical": critical,
+                        "high": high,
+                        "medium": medium,
+                        "low": low,
+                    },
+                })
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        hyper::Response::new(
+            serde_json::to_vec(&serde_json::json!({ "projects": projects }))
+                .unwrap()
+                .into(),
+        )
+    })
+    .await
+}
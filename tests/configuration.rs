@@ -0,0 +1,134 @@
+use container_registry_gateway::configuration::{self, ValidationError};
+
+#[test]
+fn invalid_configuration_reports_every_problem() {
+    let error = configuration::load(&[
+        ("oci.base_address", "registry-1.docker.io"),
+        ("oci.upstreams[0].base_address", "https://ghcr.io"),
+        ("snyk.api_key", ""),
+        ("snyk.base_address", "https://api.snyk.io"),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+        ("attestation.require_sbom", "true"),
+        ("delete.rules[0].action", "soft"),
+    ])
+    .err()
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            "snyk.api_key is required",
+            r#"oci.base_address must be an HTTP URL, got "registry-1.docker.io""#,
+            "oci.upstreams[0] requires a prefix, or a registry in mirror mode",
+            "attestation requires signature.public_keys",
+            "delete.rules[0].principals must not be empty for soft deletions",
+        ],
+        error.downcast_ref::<ValidationError>().unwrap().problems
+    );
+}
+
+#[test]
+fn malformed_patterns_keys_and_secret_files_are_reported() {
+    let error = configuration::load(&[
+        ("oci.base_address", "https://registry-1.docker.io"),
+        ("snyk.api_key_file", "/nonexistent/snyk-api-key"),
+        ("snyk.base_address", "https://api.snyk.io"),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+        ("push.immutability[0].tags[0]", "v[0-"),
+        ("push.tag_patterns[0]", "v(1"),
+        ("signature.public_keys[0]", "invalid"),
+    ])
+    .err()
+    .unwrap();
+
+    let problems = &error.downcast_ref::<ValidationError>().unwrap().problems;
+
+    assert_eq!(4, problems.len(), "{problems:?}");
+    assert!(problems[0].starts_with("snyk.api_key_file cannot be read, "));
+    assert!(problems[1].starts_with("push.immutability[0].tags is malformed, "));
+    assert!(problems[2].starts_with("push.tag_patterns is malformed, "));
+    assert!(problems[3].starts_with("signature.public_keys is malformed, "));
+}
+
+#[test]
+fn config_check_prints_redacted_configuration() {
+    let directory = std::env::temp_dir().join(format!("config-check-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let config_check = |contents: &str| {
+        let file = directory.join("config.toml");
+        std::fs::write(&file, contents).unwrap();
+
+        std::process::Command::new(env!("CARGO_BIN_EXE_container-registry-gateway-server"))
+            .args(["config", "check"])
+            .arg(&file)
+            .output()
+            .unwrap()
+    };
+
+    let output = config_check(
+        r#"
+        [oci]
+        base_address = "https://registry-1.docker.io"
+
+        [snyk]
+        api_key = "0123456789"
+        base_address = "https://api.snyk.io"
+        integration_id = "integration"
+        organization_id = "organization"
+        "#,
+    );
+    let printed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("[redacted]", printed["snyk"]["api_key"]);
+    assert_eq!("organization", printed["snyk"]["organization_id"]);
+    assert_eq!(80, printed["http_server"]["port"]);
+
+    let output = config_check(
+        r#"
+        [oci]
+        base_address = "https://registry-1.docker.io"
+
+        [snyk]
+        api_key = ""
+        base_address = "api.snyk.io"
+        integration_id = "integration"
+        organization_id = "organization"
+        "#,
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr.contains("snyk.api_key is required"), "{stderr}");
+    assert!(
+        stderr.contains("snyk.base_address must be an HTTP URL"),
+        "{stderr}"
+    );
+
+    let output = config_check(
+        r#"
+        [oci]
+        base_address = "https://registry-1.docker.io"
+
+        [push]
+        tag_patterns = ["v(1"]
+
+        [snyk]
+        api_key = "0123456789"
+        base_address = "https://api.snyk.io"
+        integration_id = "integration"
+        organization_id = "organization"
+        "#,
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert!(
+        stderr.contains("push.tag_patterns is malformed"),
+        "{stderr}"
+    );
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,251 @@
+mod common;
+
+use common::{parse_body, start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+/// Starts a mock registry accepting deletions, returning the number of deletions it received.
+///
+/// If `private`, requests without credentials are challenged.
+async fn start_registry(private: bool) -> (SocketAddr, Arc<AtomicUsize>) {
+    let deletions = Arc::new(AtomicUsize::new(0));
+
+    let registry = start_mock({
+        let deletions = deletions.clone();
+        move |parts, _| {
+            if private && !parts.headers.contains_key(hyper::header::AUTHORIZATION) {
+                hyper::Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .header(hyper::header::WWW_AUTHENTICATE, r#"Basic realm="registry""#)
+                    .body(hyper::Body::empty())
+                    .unwrap()
+            } else if parts.method == hyper::Method::DELETE {
+                deletions.fetch_add(1, Ordering::SeqCst);
+
+                hyper::Response::builder()
+                    .status(StatusCode::ACCEPTED)
+                    .body(hyper::Body::empty())
+                    .unwrap()
+            } else {
+                hyper::Response::builder()
+                    .header(
+                        hyper::header::CONTENT_TYPE,
+                        "application/vnd.oci.image.manifest.v1+json",
+                    )
+                    .header("docker-content-digest", DIGEST)
+                    .body(hyper::Body::from("{}"))
+                    .unwrap()
+            }
+        }
+    })
+    .await;
+
+    (registry, deletions)
+}
+
+/// Credentials of `admin:secret`.
+const ADMIN: &str = "Basic YWRtaW46c2VjcmV0";
+
+/// Pulls the `path` with the credentials of `admin`.
+async fn get(socket_addr: SocketAddr, path: &str) -> hyper::Response<hyper::Body> {
+    Client::new()
+        .request(
+            hyper::Request::get(format!("http://{socket_addr}{path}"))
+                .header(hyper::header::AUTHORIZATION, ADMIN)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn delete(
+    socket_addr: SocketAddr,
+    path: &str,
+    authorization: Option<&str>,
+) -> hyper::Response<hyper::Body> {
+    let mut request = hyper::Request::delete(format!("http://{socket_addr}{path}"));
+
+    if let Some(authorization) = authorization {
+        request = request.header(hyper::header::AUTHORIZATION, authorization);
+    }
+
+    Client::new()
+        .request(request.body(hyper::Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn v2_delete_of_protected_repository_is_denied() {
+    let (registry, deletions) = start_registry(false).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("delete.rules[0].repositories[0]", "prod/**"),
+        ("delete.rules[0].action", "deny"),
+    ])
+    .await;
+
+    let response = delete(
+        socket_addr,
+        &format!("/v2/prod/team/app/manifests/{DIGEST}"),
+        None,
+    )
+    .await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image deletion not allowed",
+        parse_body(response).await.errors[0].message
+    );
+
+    let response = delete(socket_addr, &format!("/v2/dev/app/blobs/{DIGEST}"), None).await;
+
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    assert_eq!(1, deletions.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn v2_delete_requires_authorized_principal() {
+    let (registry, deletions) = start_registry(false).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("delete.rules[0].principals[0]", "admin"),
+    ])
+    .await;
+
+    let path = format!("/v2/app/manifests/{DIGEST}");
+
+    // developer:secret
+    let response = delete(socket_addr, &path, Some("Basic ZGV2ZWxvcGVyOnNlY3JldA==")).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(0, deletions.load(Ordering::SeqCst));
+
+    let response = delete(socket_addr, &path, Some(ADMIN)).await;
+
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    assert_eq!(1, deletions.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn v2_soft_deleted_manifest_is_hidden_and_journaled() {
+    let (registry, deletions) = start_registry(true).await;
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let journal = std::env::temp_dir().join(format!("crg-delete-{}.jsonl", registry.port()));
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("delete.journal", journal.to_str().unwrap()),
+        ("delete.rules[0].action", "soft"),
+        ("delete.rules[0].principals[0]", "admin"),
+        ("snyk.base_address", &format!("http://{snyk}")),
+    ])
+    .await;
+
+    let path = format!("/v2/app/manifests/{DIGEST}");
+
+    let response = delete(socket_addr, &path, None).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    // developer:secret
+    let response = delete(socket_addr, &path, Some("Basic ZGV2ZWxvcGVyOnNlY3JldA==")).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        StatusCode::OK,
+        get(socket_addr, "/v2/app/manifests/latest").await.status()
+    );
+    assert!(!journal.exists());
+
+    let response = delete(socket_addr, &path, Some(ADMIN)).await;
+
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    assert_eq!(0, deletions.load(Ordering::SeqCst));
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        get(socket_addr, "/v2/app/manifests/latest").await.status()
+    );
+    assert!(std::fs::read_to_string(&journal).unwrap().contains(DIGEST));
+
+    std::fs::remove_file(journal).unwrap();
+}
+
+#[tokio::test]
+async fn v2_soft_deleted_manifest_is_restored() {
+    let (registry, _) = start_registry(true).await;
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let journal = std::env::temp_dir().join(format!("crg-restore-{}.jsonl", registry.port()));
+    let registry = format!("http://{registry}");
+    let snyk = format!("http://{snyk}");
+    let overrides = [
+        ("oci.base_address", registry.as_str()),
+        ("delete.journal", journal.to_str().unwrap()),
+        ("delete.rules[0].action", "soft"),
+        ("delete.rules[0].principals[0]", "admin"),
+        ("snyk.base_address", snyk.as_str()),
+    ];
+
+    let socket_addr = start_server_with(&overrides).await;
+
+    let restore = |authorization: &'static str| async move {
+        Client::new()
+            .request(
+                hyper::Request::post(format!("http://{socket_addr}/delete/restore"))
+                    .header(hyper::header::AUTHORIZATION, authorization)
+                    .header(hyper::header::CONTENT_TYPE, "application/json")
+                    .body(hyper::Body::from(
+                        serde_json::json!({ "repository": "app", "reference": DIGEST }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    };
+
+    let path = format!("/v2/app/manifests/{DIGEST}");
+
+    assert_eq!(
+        StatusCode::ACCEPTED,
+        delete(socket_addr, &path, Some(ADMIN)).await.status()
+    );
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        get(socket_addr, &path).await.status()
+    );
+
+    // developer:secret
+    assert_eq!(
+        StatusCode::FORBIDDEN,
+        restore("Basic ZGV2ZWxvcGVyOnNlY3JldA==").await
+    );
+    assert_eq!(
+        StatusCode::NOT_FOUND,
+        get(socket_addr, &path).await.status()
+    );
+
+    assert_eq!(StatusCode::NO_CONTENT, restore(ADMIN).await);
+    assert_eq!(StatusCode::OK, get(socket_addr, &path).await.status());
+    assert_eq!(StatusCode::NOT_FOUND, restore(ADMIN).await);
+
+    // the restoration is journaled, so it survives restarts.
+    let socket_addr = start_server_with(&overrides).await;
+
+    assert_eq!(StatusCode::OK, get(socket_addr, &path).await.status());
+
+    std::fs::remove_file(journal).unwrap();
+}
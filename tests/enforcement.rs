@@ -0,0 +1,75 @@
+mod common;
+
+use common::{get_as_admin, start_mock, start_server_with, start_snyk, ADMIN};
+use hyper::{client::Client, StatusCode};
+use std::net::SocketAddr;
+
+async fn get(socket_addr: SocketAddr, path: &str) -> hyper::Response<hyper::Body> {
+    Client::new()
+        .get(format!("http://{socket_addr}{path}").parse().unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn v2_denial_is_enforced_according_to_repository_mode() {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            )
+            .body(hyper::Body::from("{}"))
+            .unwrap()
+    })
+    .await;
+    let snyk = start_snyk(|_| None).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("enforcement.rules[0].repositories[0]", "canary/**"),
+        ("enforcement.rules[0].mode", "warn"),
+        ("enforcement.rules[1].repositories[0]", "staging/**"),
+        ("enforcement.rules[1].mode", "audit"),
+        ADMIN[0],
+        ADMIN[1],
+    ])
+    .await;
+
+    let response = get(socket_addr, "/v2/canary/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(
+        r#"299 - "Image not monitored for vulnerabilities""#,
+        response.headers()[hyper::header::WARNING]
+    );
+
+    let response = get(socket_addr, "/v2/staging/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(!response.headers().contains_key(hyper::header::WARNING));
+
+    let response = get(socket_addr, "/v2/production/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        get(socket_addr, "/metrics").await.status()
+    );
+
+    let metrics = hyper::body::to_bytes(get_as_admin(socket_addr, "/metrics").await)
+        .await
+        .unwrap();
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+
+    for mode in ["audit", "enforce", "warn"] {
+        assert!(
+            metrics.contains(&format!(
+                "container_registry_gateway_admission_denials_total{{mode=\"{mode}\"}} 1"
+            )),
+            "{metrics}"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use container_registry_gateway::fixture;
+use std::path::PathBuf;
+
+/// Writes the policy and fixtures into a new directory, returning the paths of the policy and of
+/// the fixtures directory.
+fn write(name: &str, policy: &str, fixtures: &[(&str, serde_json::Value)]) -> (PathBuf, PathBuf) {
+    let directory = std::env::temp_dir().join(format!("crg-fixture-{name}-{}", std::process::id()));
+    let fixtures_directory = directory.join("fixtures");
+
+    std::fs::create_dir_all(&fixtures_directory).unwrap();
+
+    let policy_path = directory.join("policy.toml");
+    std::fs::write(&policy_path, policy).unwrap();
+
+    for (name, fixture) in fixtures {
+        std::fs::write(
+            fixtures_directory.join(name),
+            serde_json::to_vec(fixture).unwrap(),
+        )
+        .unwrap();
+    }
+
+    (policy_path, fixtures_directory)
+}
+
+fn project(criticality: &[&str], [critical, high, medium, low]: [u32; 4]) -> serde_json::Value {
+    serde_json::json!({
+        "name": "app:v1",
+        "attributes": { "criticality": criticality },
+        "issueCountsBySeverity": {
+            "critical": critical,
+            "high": high,
+            "medium": medium,
+            "low": low,
+        },
+    })
+}
+
+#[test]
+fn fixtures_matching_policy_are_reported_without_mismatches() {
+    let (policy, fixtures) = write(
+        "matching",
+        "[vulnerability]\nhigh = 2\nlow = 10\n",
+        &[
+            (
+                "below-thresholds.json",
+                serde_json::json!({
+                    "scan": { "projects": [project(&[], [0, 2, 0, 10])] },
+                    "expected": { "admitted": true },
+                }),
+            ),
+            (
+                "above-threshold.json",
+                serde_json::json!({
+                    "scan": project(&[], [0, 3, 0, 0]),
+                    "expected": {
+                        "admitted": false,
+                        "message": "Image exceeded vulnerability threshold high",
+                    },
+                }),
+            ),
+            (
+                "accepted-criticality.json",
+                serde_json::json!({
+                    "scan": project(&["critical"], [5, 5, 5, 5]),
+                    "expected": { "admitted": true },
+                }),
+            ),
+            (
+                "not-monitored.json",
+                serde_json::json!({
+                    "scan": { "projects": [] },
+                    "expected": { "admitted": false },
+                }),
+            ),
+            ("README.md", serde_json::json!("not a fixture")),
+        ],
+    );
+
+    let report = fixture::evaluate(&policy, &fixtures).unwrap();
+
+    assert_eq!(4, report.fixtures);
+    assert!(report.mismatches.is_empty(), "{report}");
+}
+
+#[test]
+fn fixtures_not_matching_policy_are_reported_as_mismatches() {
+    let (policy, fixtures) = write(
+        "mismatching",
+        "[vulnerability]\nmedium = 1\n",
+        &[
+            (
+                "medium.json",
+                serde_json::json!({
+                    "scan": project(&[], [0, 0, 2, 0]),
+                    "expected": { "admitted": true },
+                }),
+            ),
+            (
+                "low.json",
+                serde_json::json!({
+                    "scan": project(&[], [0, 0, 0, 1]),
+                    "expected": {
+                        "admitted": false,
+                        "message": "Image exceeded vulnerability threshold medium",
+                    },
+                }),
+            ),
+        ],
+    );
+
+    let report = fixture::evaluate(&policy, &fixtures).unwrap();
+
+    assert_eq!(2, report.mismatches.len());
+    assert!(report.mismatches[0].fixture.ends_with("low.json"));
+    assert_eq!(
+        Some("Image exceeded vulnerability threshold low"),
+        report.mismatches[0].actual.message.as_deref()
+    );
+    assert!(report.mismatches[1].fixture.ends_with("medium.json"));
+    assert!(!report.mismatches[1].actual.admitted);
+}
@@ -0,0 +1,354 @@
+mod common;
+
+use common::{start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use std::net::SocketAddr;
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+/// Starts the gateway in front of a mock registry and a mock Snyk API, which reports critical
+/// vulnerabilities in `app:vulnerable`.
+async fn start() -> SocketAddr {
+    start_with(&[("kubernetes.registries[0]", "gateway.example.com")]).await
+}
+
+/// Starts the gateway as [`start`] does, with the `overrides`.
+///
+/// The mock registry challenges requests for repositories under `private/`.
+async fn start_with(overrides: &[(&str, &str)]) -> SocketAddr {
+    let registry = start_mock(|parts, _| {
+        if parts.uri.path().starts_with("/v2/private/") {
+            return hyper::Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(hyper::Body::empty())
+                .unwrap();
+        }
+
+        hyper::Response::builder()
+            .header("docker-content-digest", DIGEST)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let snyk = start_snyk(|name| match name {
+        "app:vulnerable" | "library/app:vulnerable" => Some([1, 0, 0, 0]),
+        "app:v1" | "sidecar:v1" | "library/busybox:latest" | "private/app:v1" => Some([0, 0, 0, 0]),
+        _ => None,
+    })
+    .await;
+
+    let registry = format!("http://{registry}");
+    let snyk = format!("http://{snyk}");
+
+    let mut configuration = vec![
+        ("oci.base_address", registry.as_str()),
+        ("snyk.base_address", snyk.as_str()),
+        ("oci.registry", "docker.io"),
+        ("kubernetes.gateway", "gateway.example.com"),
+    ];
+    configuration.extend_from_slice(overrides);
+
+    start_server_with(&configuration).await
+}
+
+async fn review(
+    socket_addr: SocketAddr,
+    webhook: &str,
+    kind: &str,
+    object: serde_json::Value,
+) -> serde_json::Value {
+    let review = serde_json::json!({
+        "apiVersion": "admission.k8s.io/v1",
+        "kind": "AdmissionReview",
+        "request": {
+            "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+            "kind": { "group": "apps", "version": "v1", "kind": kind },
+            "object": object,
+        },
+    });
+
+    let response = Client::new()
+        .request(
+            hyper::Request::post(format!("http://{socket_addr}/admission/{webhook}"))
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&review).unwrap().into())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    let body = hyper::body::to_bytes(response).await.unwrap();
+    let review: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(
+        "705ab4f5-6393-11e8-b7cc-42010a800002",
+        review["response"]["uid"]
+    );
+
+    review["response"].clone()
+}
+
+fn pod_spec(images: &[&str]) -> serde_json::Value {
+    serde_json::json!({
+        "initContainers": [{ "name": "init", "image": images[0] }],
+        "containers": images[1..]
+            .iter()
+            .map(|image| serde_json::json!({ "name": "main", "image": image }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[tokio::test]
+async fn admission_of_pod_with_admitted_images_is_allowed() {
+    let socket_addr = start().await;
+
+    let response = review(
+        socket_addr,
+        "validate",
+        "Pod",
+        serde_json::json!({
+            "spec": pod_spec(&[
+                "gateway.example.com/sidecar:v1",
+                &format!("gateway.example.com/app:v1@{DIGEST}"),
+                "docker.io/library/busybox:latest",
+            ]),
+        }),
+    )
+    .await;
+
+    assert_eq!(true, response["allowed"]);
+}
+
+#[tokio::test]
+async fn admission_of_workload_with_vulnerable_image_is_denied() {
+    let socket_addr = start().await;
+
+    let response = review(
+        socket_addr,
+        "validate",
+        "Deployment",
+        serde_json::json!({
+            "spec": {
+                "template": {
+                    "spec": pod_spec(&[
+                        "gateway.example.com/sidecar:v1",
+                        "gateway.example.com/app:vulnerable",
+                    ]),
+                },
+            },
+        }),
+    )
+    .await;
+
+    assert_eq!(false, response["allowed"]);
+    assert_eq!(403, response["status"]["code"]);
+    assert_eq!(
+        "gateway.example.com/app:vulnerable: Image exceeded vulnerability threshold critical",
+        response["status"]["message"]
+    );
+}
+
+#[tokio::test]
+async fn admission_of_cron_job_with_unmonitored_init_container_is_denied() {
+    let socket_addr = start().await;
+
+    let response = review(
+        socket_addr,
+        "validate",
+        "CronJob",
+        serde_json::json!({
+            "spec": {
+                "jobTemplate": {
+                    "spec": {
+                        "template": {
+                            "spec": pod_spec(&[
+                                "gateway.example.com/unknown:v1",
+                                "gateway.example.com/app:v1",
+                            ]),
+                        },
+                    },
+                },
+            },
+        }),
+    )
+    .await;
+
+    assert_eq!(false, response["allowed"]);
+    assert_eq!(
+        "gateway.example.com/unknown:v1: Image not monitored for vulnerabilities",
+        response["status"]["message"]
+    );
+}
+
+#[tokio::test]
+async fn admission_without_registries_evaluates_images_of_their_upstream() {
+    let socket_addr = start_with(&[]).await;
+
+    let response = review(
+        socket_addr,
+        "validate",
+        "Pod",
+        serde_json::json!({
+            "spec": pod_spec(&[
+                "quay.io/app:vulnerable",
+                "docker.io/app:vulnerable",
+                "private/app:v1",
+                "gateway.example.com/sidecar:v1",
+            ]),
+        }),
+    )
+    .await;
+
+    assert_eq!(false, response["allowed"]);
+    assert_eq!(
+        "docker.io/app:vulnerable: Image exceeded vulnerability threshold critical; \
+         private/app:v1: Image digest could not be resolved; \
+         quay.io/app:vulnerable: Registry quay.io is not served by the gateway",
+        response["status"]["message"]
+    );
+}
+
+#[tokio::test]
+async fn admission_evaluates_images_on_mirror_upstream_of_their_registry() {
+    let ghcr = start_mock(|_, _| {
+        hyper::Response::builder()
+            .header("docker-content-digest", DIGEST)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let socket_addr = start_with(&[
+        ("oci.mirror", "true"),
+        ("oci.upstreams[0].registry", "ghcr.io"),
+        ("oci.upstreams[0].base_address", &format!("http://{ghcr}")),
+    ])
+    .await;
+
+    let response = review(
+        socket_addr,
+        "validate",
+        "Pod",
+        serde_json::json!({
+            "spec": pod_spec(&["ghcr.io/private/app:v1"]),
+        }),
+    )
+    .await;
+
+    assert_eq!(true, response["allowed"], "{response}");
+}
+
+#[tokio::test]
+async fn admission_mutation_rewrites_images_to_gateway_pinned_by_digest() {
+    use base64::Engine as _;
+
+    let socket_addr = start().await;
+
+    let response = review(
+        socket_addr,
+        "mutate",
+        "Pod",
+        serde_json::json!({
+            "spec": pod_spec(&[
+                "gateway.example.com/sidecar:v1",
+                "busybox",
+                "quay.io/team/app:v1",
+            ]),
+        }),
+    )
+    .await;
+
+    assert_eq!(true, response["allowed"]);
+    assert_eq!("JSONPatch", response["patchType"]);
+
+    let patch: serde_json::Value = serde_json::from_slice(
+        &base64::engine::general_purpose::STANDARD
+            .decode(response["patch"].as_str().unwrap())
+            .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        serde_json::json!([
+            {
+                "op": "replace",
+                "path": "/spec/initContainers/0/image",
+                "value": format!("gateway.example.com/sidecar@{DIGEST}"),
+            },
+            {
+                "op": "replace",
+                "path": "/spec/containers/0/image",
+                "value": format!("gateway.example.com/library/busybox@{DIGEST}"),
+            },
+        ]),
+        patch
+    );
+}
+
+#[tokio::test]
+async fn admission_mutation_keeps_images_of_upstreams_without_prefix() {
+    use base64::Engine as _;
+
+    let socket_addr = start_with(&[
+        ("kubernetes.registries[0]", "gateway.example.com"),
+        ("oci.mirror", "true"),
+        ("oci.upstreams[0].registry", "ghcr.io"),
+        ("oci.upstreams[0].base_address", "http://127.0.0.1:9"),
+    ])
+    .await;
+
+    let response = review(
+        socket_addr,
+        "mutate",
+        "Pod",
+        serde_json::json!({
+            "spec": pod_spec(&["gateway.example.com/busybox:latest", "ghcr.io/org/app:1"]),
+        }),
+    )
+    .await;
+
+    assert_eq!(true, response["allowed"]);
+
+    let patch: serde_json::Value = serde_json::from_slice(
+        &base64::engine::general_purpose::STANDARD
+            .decode(response["patch"].as_str().unwrap())
+            .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        serde_json::json!([
+            {
+                "op": "replace",
+                "path": "/spec/initContainers/0/image",
+                "value": format!("gateway.example.com/busybox@{DIGEST}"),
+            },
+        ]),
+        patch
+    );
+}
+
+#[tokio::test]
+async fn admission_mutation_of_vulnerable_image_is_denied() {
+    let socket_addr = start().await;
+
+    let response = review(
+        socket_addr,
+        "mutate",
+        "Pod",
+        serde_json::json!({
+            "spec": pod_spec(&["gateway.example.com/sidecar:v1", "app:vulnerable"]),
+        }),
+    )
+    .await;
+
+    assert_eq!(false, response["allowed"]);
+    assert_eq!(
+        "app:vulnerable: Image exceeded vulnerability threshold critical",
+        response["status"]["message"]
+    );
+    assert!(response.get("patch").is_none());
+}
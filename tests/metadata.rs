@@ -0,0 +1,138 @@
+mod common;
+
+use common::{parse_body, start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use sha2::Digest as _;
+use std::collections::HashMap;
+
+fn digest(content: &[u8]) -> String {
+    format!("sha256:{:x}", sha2::Sha256::digest(content))
+}
+
+/// Pulls `app:latest` through the gateway, from a mock registry serving the image configuration.
+async fn pull(configuration: &serde_json::Value) -> hyper::Response<hyper::Body> {
+    let configuration = serde_json::to_vec(configuration).unwrap();
+
+    let manifest = serde_json::to_vec(&serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(&configuration),
+            "size": configuration.len(),
+        },
+        "layers": [],
+        "annotations": {
+            "org.opencontainers.image.base.name": "docker.io/library/alpine:3.17",
+        },
+    }))
+    .unwrap();
+
+    let mut contents = HashMap::new();
+    contents.insert("/v2/app/manifests/latest".to_string(), manifest.clone());
+    contents.insert(format!("/v2/app/manifests/{}", digest(&manifest)), manifest);
+    contents.insert(
+        format!("/v2/app/blobs/{}", digest(&configuration)),
+        configuration,
+    );
+
+    let registry = start_mock(move |parts, _| match contents.get(parts.uri.path()) {
+        Some(body) => hyper::Response::builder()
+            .header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            )
+            .header("docker-content-digest", digest(body))
+            .body(body.clone().into())
+            .unwrap(),
+        None => hyper::Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(hyper::Body::empty())
+            .unwrap(),
+    })
+    .await;
+
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        (
+            "metadata.required_labels[0]",
+            "org.opencontainers.image.source",
+        ),
+        ("metadata.base_images[0]", "docker.io/library/alpine"),
+        ("metadata.max_age_days", "30"),
+        ("metadata.deny_root", "true"),
+        ("metadata.allowed_ports[0]", "8080"),
+    ])
+    .await;
+
+    Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/app/manifests/latest")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+fn configuration(user: &str) -> serde_json::Value {
+    serde_json::json!({
+        "created": humantime::format_rfc3339(std::time::SystemTime::now()).to_string(),
+        "architecture": "amd64",
+        "os": "linux",
+        "config": {
+            "User": user,
+            "ExposedPorts": { "8080/tcp": {} },
+            "Labels": { "org.opencontainers.image.source": "https://github.com/example/app" },
+        },
+    })
+}
+
+#[tokio::test]
+async fn v2_manifest_satisfying_metadata_policy_is_admitted() {
+    let response = pull(&configuration("1000:1000")).await;
+
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn v2_manifest_running_as_root_is_denied() {
+    let response = pull(&configuration("root")).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image runs as root",
+        parse_body(response).await.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_manifest_without_source_label_is_denied() {
+    let mut configuration = configuration("1000");
+    configuration["config"]["Labels"] = serde_json::Value::Null;
+
+    let response = pull(&configuration).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image label org.opencontainers.image.source not found",
+        parse_body(response).await.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_manifest_exceeding_maximum_age_is_denied() {
+    let mut configuration = configuration("1000");
+    configuration["created"] = "2015-10-31T22:22:56.015925234Z".into();
+
+    let response = pull(&configuration).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image exceeded maximum age",
+        parse_body(response).await.errors[0].message
+    );
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use common::{start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+type Received = Arc<Mutex<Vec<serde_json::Value>>>;
+
+/// Starts a webhook sink, failing the first `failures` notifications it receives.
+async fn start_sink(failures: usize) -> (SocketAddr, Received) {
+    let received = Received::default();
+
+    let sink = start_mock({
+        let received = received.clone();
+        move |_, body| {
+            let mut received = received.lock().unwrap();
+            received.push(serde_json::from_slice(&body).unwrap());
+
+            hyper::Response::builder()
+                .status(if received.len() > failures {
+                    StatusCode::OK
+                } else {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })
+                .body(hyper::Body::empty())
+                .unwrap()
+        }
+    })
+    .await;
+
+    (sink, received)
+}
+
+async fn start_registry() -> SocketAddr {
+    start_mock(|parts, _| {
+        hyper::Response::builder()
+            .status(if parts.method == hyper::Method::PUT {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            })
+            .header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            )
+            .header("docker-content-digest", DIGEST)
+            .body(hyper::Body::from("{}"))
+            .unwrap()
+    })
+    .await
+}
+
+/// Waits for the sink to receive `count` notifications.
+async fn wait_for(received: &Received, count: usize) -> Vec<serde_json::Value> {
+    for _ in 0..50 {
+        if received.lock().unwrap().len() >= count {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    received.lock().unwrap().clone()
+}
+
+async fn pull(socket_addr: SocketAddr) -> StatusCode {
+    Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/app/manifests/latest")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn v2_denied_pull_is_notified_once_per_deduplication_window() {
+    let registry = start_registry().await;
+    let snyk = start_snyk(|_| None).await;
+    let (sink, received) = start_sink(0).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("notification.targets[0].url", &format!("http://{sink}")),
+        ("notification.targets[0].events[0]", "denied"),
+    ])
+    .await;
+
+    assert_eq!(StatusCode::FORBIDDEN, pull(socket_addr).await);
+    assert_eq!(StatusCode::FORBIDDEN, pull(socket_addr).await);
+
+    wait_for(&received, 1).await;
+
+    // a duplicate would be delivered shortly after the first notification.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let received = received.lock().unwrap().clone();
+
+    assert_eq!(1, received.len());
+    assert_eq!("denied", received[0]["event"]);
+    assert_eq!("app", received[0]["repository"]);
+    assert_eq!("latest", received[0]["reference"]);
+    assert_eq!(
+        "Image not monitored for vulnerabilities",
+        received[0]["message"]
+    );
+}
+
+#[tokio::test]
+async fn v2_push_is_notified_with_template_to_matching_repositories() {
+    let registry = start_registry().await;
+    let snyk = start_mock(|_, _| {
+        hyper::Response::builder()
+            .status(StatusCode::CREATED)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+    let (sink, received) = start_sink(0).await;
+    let (other_sink, other_received) = start_sink(0).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("notification.targets[0].url", &format!("http://{sink}")),
+        ("notification.targets[0].repositories[0]", "app"),
+        (
+            "notification.targets[0].template",
+            r#"{"text": "{{event}} {{repository}}:{{reference}}@{{digest}}"}"#,
+        ),
+        (
+            "notification.targets[1].url",
+            &format!("http://{other_sink}"),
+        ),
+        ("notification.targets[1].repositories[0]", "other/**"),
+    ])
+    .await;
+
+    let response = Client::new()
+        .request(
+            hyper::Request::put(format!("http://{socket_addr}/v2/app/manifests/v1"))
+                .body(hyper::Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::CREATED, response.status());
+
+    let received = wait_for(&received, 1).await;
+
+    assert_eq!(
+        serde_json::json!({ "text": format!("pushed app:v1@{DIGEST}") }),
+        received[0]
+    );
+    assert!(other_received.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn v2_failed_notification_is_retried() {
+    let registry = start_registry().await;
+    let snyk = start_snyk(|_| None).await;
+    let (sink, received) = start_sink(1).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("notification.targets[0].url", &format!("http://{sink}")),
+    ])
+    .await;
+
+    assert_eq!(StatusCode::FORBIDDEN, pull(socket_addr).await);
+
+    let received = wait_for(&received, 2).await;
+
+    assert_eq!(2, received.len());
+    assert_eq!(received[0], received[1]);
+}
@@ -0,0 +1,99 @@
+use container_registry_gateway::oci::manifest::{
+    accepted, is_acceptable, ImageConfiguration, Manifest, DOCKER_MANIFEST, OCI_IMAGE_INDEX,
+};
+
+#[test]
+fn manifest_without_media_type_is_detected() {
+    let manifest = Manifest::from_slice(
+        None,
+        br#"{
+            "schemaVersion": 2,
+            "manifests": [{
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "digest": "sha256:2222222222222222222222222222222222222222222222222222222222222222",
+                "size": 7143,
+                "platform": { "architecture": "arm64", "os": "linux", "variant": "v8" }
+            }],
+            "annotations": { "org.opencontainers.image.source": "https://github.com/example/app" }
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(OCI_IMAGE_INDEX, manifest.media_type());
+    assert_eq!(
+        Some("v8"),
+        manifest.manifests()[0]
+            .platform
+            .as_ref()
+            .and_then(|platform| platform.variant.as_deref())
+    );
+    assert_eq!(
+        "https://github.com/example/app",
+        manifest.annotations()["org.opencontainers.image.source"]
+    );
+}
+
+#[test]
+fn docker_manifest_layers_are_parsed() {
+    let manifest = Manifest::from_slice(
+        Some(DOCKER_MANIFEST),
+        br#"{
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {
+                "mediaType": "application/vnd.docker.container.image.v1+json",
+                "digest": "sha256:3333333333333333333333333333333333333333333333333333333333333333",
+                "size": 1469
+            },
+            "layers": [{
+                "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip",
+                "digest": "sha256:4444444444444444444444444444444444444444444444444444444444444444",
+                "size": 2814446
+            }]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(DOCKER_MANIFEST, manifest.media_type());
+    assert_eq!(1469, manifest.config().unwrap().size);
+    assert_eq!(2_814_446, manifest.layers()[0].size);
+}
+
+#[test]
+fn image_configuration_with_null_collections_is_parsed() {
+    let configuration = serde_json::from_str::<ImageConfiguration>(
+        r#"{
+            "created": "2022-12-06T00:00:00Z",
+            "architecture": "amd64",
+            "os": "linux",
+            "config": {
+                "User": "1000",
+                "ExposedPorts": { "8080/tcp": {} },
+                "Env": null,
+                "Labels": null
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let config = configuration.config.unwrap();
+
+    assert_eq!(Some("1000"), config.user.as_deref());
+    assert!(config.exposed_ports.contains_key("8080/tcp"));
+    assert!(config.labels.is_empty());
+}
+
+#[test]
+fn media_type_is_negotiated_from_accept_header() {
+    let accepted = accepted([
+        "application/vnd.oci.image.index.v1+json, application/vnd.docker.distribution.manifest.v2+json;q=0.9",
+    ]);
+
+    assert!(is_acceptable(&accepted, OCI_IMAGE_INDEX));
+    assert!(is_acceptable(&accepted, DOCKER_MANIFEST));
+    assert!(!is_acceptable(
+        &accepted,
+        "application/vnd.docker.distribution.manifest.list.v2+json"
+    ));
+    assert!(is_acceptable(&[], OCI_IMAGE_INDEX));
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::{parse_body, start_mock, start_server_with};
+use hyper::{client::Client, StatusCode};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Pushes `manifest` as `app:<tag>` through the gateway, returning the response and the number of
+/// pushes that reached the upstream.
+async fn push(tag: &str, manifest: &serde_json::Value) -> (hyper::Response<hyper::Body>, usize) {
+    push_as(None, tag, manifest).await
+}
+
+/// Pushes `manifest` as `app:<tag>` through the gateway with the `authorization`.
+async fn push_as(
+    authorization: Option<&str>,
+    tag: &str,
+    manifest: &serde_json::Value,
+) -> (hyper::Response<hyper::Body>, usize) {
+    let pushes = Arc::new(AtomicUsize::new(0));
+
+    let registry = start_mock({
+        let pushes = pushes.clone();
+        move |parts, _| match (parts.method, parts.uri.path()) {
+            (hyper::Method::PUT, _) => {
+                pushes.fetch_add(1, Ordering::SeqCst);
+                hyper::Response::builder()
+                    .status(StatusCode::CREATED)
+                    .body(hyper::Body::empty())
+                    .unwrap()
+            }
+            (hyper::Method::HEAD, "/v2/app/manifests/v1.0.0") => hyper::Response::builder()
+                .header(
+                    "docker-content-digest",
+                    "sha256:9999999999999999999999999999999999999999999999999999999999999999",
+                )
+                .body(hyper::Body::empty())
+                .unwrap(),
+            (_, _) => hyper::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        }
+    })
+    .await;
+
+    let snyk = start_mock(|_, _| {
+        hyper::Response::builder()
+            .status(StatusCode::CREATED)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("push.immutability[0].repositories[0]", "app"),
+        ("push.immutability[0].tags[0]", r"v\d+\.\d+\.\d+"),
+        ("push.immutability[0].principals[0]", "release-bot"),
+        ("push.tag_patterns[0]", r"v\d+\.\d+\.\d+"),
+        ("push.tag_patterns[1]", "main"),
+        ("push.deny_foreign_layers", "true"),
+        ("push.max_size", "1048576"),
+    ])
+    .await;
+
+    let mut request = hyper::Request::put(format!("http://{socket_addr}/v2/app/manifests/{tag}"))
+        .header(
+            hyper::header::CONTENT_TYPE,
+            "application/vnd.oci.image.manifest.v1+json",
+        );
+
+    if let Some(authorization) = authorization {
+        request = request.header(hyper::header::AUTHORIZATION, authorization);
+    }
+
+    let response = Client::new()
+        .request(
+            request
+                .body(serde_json::to_vec(manifest).unwrap().into())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    (response, pushes.load(Ordering::SeqCst))
+}
+
+fn manifest(layer_media_type: &str, layer_size: u64) -> serde_json::Value {
+    serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": "sha256:1111111111111111111111111111111111111111111111111111111111111111",
+            "size": 1024,
+        },
+        "layers": [{
+            "mediaType": layer_media_type,
+            "digest": "sha256:2222222222222222222222222222222222222222222222222222222222222222",
+            "size": layer_size,
+        }],
+    })
+}
+
+const LAYER: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+
+#[tokio::test]
+async fn v2_manifest_push_satisfying_policy_is_forwarded() {
+    let (response, pushes) = push("main", &manifest(LAYER, 4096)).await;
+
+    assert_eq!(StatusCode::CREATED, response.status());
+    assert_eq!(1, pushes);
+}
+
+#[tokio::test]
+async fn v2_manifest_push_of_disallowed_tag_is_denied() {
+    let (response, pushes) = push("latest", &manifest(LAYER, 4096)).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(0, pushes);
+    assert_eq!(
+        "Image tag latest not allowed",
+        parse_body(response).await.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_manifest_push_overwriting_immutable_tag_is_denied() {
+    let (response, pushes) = push("v1.0.0", &manifest(LAYER, 4096)).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(0, pushes);
+    assert_eq!(
+        "Image tag v1.0.0 is immutable",
+        parse_body(response).await.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_manifest_push_overwriting_immutable_tag_by_privileged_principal_is_forwarded() {
+    // release-bot:secret
+    let (response, pushes) = push_as(
+        Some("Basic cmVsZWFzZS1ib3Q6c2VjcmV0"),
+        "v1.0.0",
+        &manifest(LAYER, 4096),
+    )
+    .await;
+
+    assert_eq!(StatusCode::CREATED, response.status());
+    assert_eq!(1, pushes);
+}
+
+#[tokio::test]
+async fn v2_manifest_push_of_new_immutable_tag_is_forwarded() {
+    let (response, pushes) = push("v1.0.1", &manifest(LAYER, 4096)).await;
+
+    assert_eq!(StatusCode::CREATED, response.status());
+    assert_eq!(1, pushes);
+}
+
+#[tokio::test]
+async fn v2_manifest_push_with_foreign_layer_is_denied() {
+    let (response, pushes) = push(
+        "main",
+        &manifest(
+            "application/vnd.docker.image.rootfs.foreign.diff.tar.gzip",
+            4096,
+        ),
+    )
+    .await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(0, pushes);
+}
+
+#[tokio::test]
+async fn v2_manifest_push_exceeding_maximum_size_is_denied() {
+    let (response, pushes) = push("main", &manifest(LAYER, 2 * 1024 * 1024)).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(0, pushes);
+    assert_eq!(
+        "Image exceeded maximum size of 1048576 bytes",
+        parse_body(response).await.errors[0].message
+    );
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use common::{get_as_admin, parse_body, start_mock, start_server_with, ADMIN};
+use hyper::{client::Client, StatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+/// Starts the gateway in front of a mock registry and a mock Snyk API, which monitors the image
+/// once `scanned` is set.
+async fn start(overrides: &[(&str, &str)], scanned: Arc<AtomicBool>) -> SocketAddr {
+    let registry = start_mock(|parts, _| {
+        hyper::Response::builder()
+            .status(if parts.method == hyper::Method::PUT {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            })
+            .header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            )
+            .header("docker-content-digest", DIGEST)
+            .body(hyper::Body::from("{}"))
+            .unwrap()
+    })
+    .await;
+
+    let snyk = start_mock(move |parts, _| {
+        if parts.uri.path().ends_with("/import") {
+            return hyper::Response::builder()
+                .status(StatusCode::CREATED)
+                .body(hyper::Body::empty())
+                .unwrap();
+        }
+
+        let projects = if scanned.load(Ordering::SeqCst) {
+            serde_json::json!([{
+                "name": "app:v1",
+                "attributes": { "criticality": [] },
+                "issueCountsBySeverity": { "critical": 0, "high": 0, "medium": 0, "low": 0 },
+            }])
+        } else {
+            serde_json::json!([])
+        };
+
+        hyper::Response::new(
+            serde_json::to_vec(&serde_json::json!({ "projects": projects }))
+                .unwrap()
+                .into(),
+        )
+    })
+    .await;
+
+    let mut overrides = overrides.to_vec();
+    let registry = format!("http://{registry}");
+    let snyk = format!("http://{snyk}");
+    overrides.push(("oci.base_address", &registry));
+    overrides.push(("snyk.base_address", &snyk));
+    overrides.extend(ADMIN);
+
+    let socket_addr = start_server_with(&overrides).await;
+
+    let response = Client::new()
+        .request(
+            hyper::Request::put(format!("http://{socket_addr}/v2/app/manifests/v1"))
+                .body(hyper::Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::CREATED, response.status());
+
+    socket_addr
+}
+
+async fn get(socket_addr: SocketAddr, path: &str) -> hyper::Response<hyper::Body> {
+    Client::new()
+        .get(format!("http://{socket_addr}{path}").parse().unwrap())
+        .await
+        .unwrap()
+}
+
+async fn pending(socket_addr: SocketAddr) -> Vec<serde_json::Value> {
+    let body = hyper::body::to_bytes(get_as_admin(socket_addr, "/quarantine").await)
+        .await
+        .unwrap();
+
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn v2_pushed_manifest_is_quarantined_until_scanned() {
+    let scanned = Arc::new(AtomicBool::new(false));
+
+    let socket_addr = start(&[("quarantine.poll_interval", "7")], scanned.clone()).await;
+
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        get(socket_addr, "/quarantine").await.status()
+    );
+
+    let held = pending(socket_addr).await;
+
+    assert_eq!(1, held.len());
+    assert_eq!(DIGEST, held[0]["digest"]);
+    assert_eq!("v1", held[0]["tag"]);
+
+    let response = get(socket_addr, "/v2/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+    assert_eq!("7", response.headers()[hyper::header::RETRY_AFTER]);
+    assert_eq!(
+        "Image quarantined until scan completes",
+        parse_body(response).await.errors[0].message
+    );
+
+    scanned.store(true, Ordering::SeqCst);
+
+    let response = get(socket_addr, "/v2/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(pending(socket_addr).await.is_empty());
+}
+
+#[tokio::test]
+async fn v2_pull_of_quarantined_manifest_waits_for_scan() {
+    let scanned = Arc::new(AtomicBool::new(false));
+
+    let socket_addr = start(
+        &[("quarantine.wait", "10"), ("quarantine.poll_interval", "1")],
+        scanned.clone(),
+    )
+    .await;
+
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        scanned.store(true, Ordering::SeqCst);
+    });
+
+    let response = get(socket_addr, "/v2/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+}
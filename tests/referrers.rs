@@ -0,0 +1,119 @@
+mod common;
+
+use common::{start_mock, start_server_with};
+use container_registry_gateway::oci::manifest::{ImageIndex, OCI_IMAGE_INDEX};
+use hyper::{client::Client, StatusCode};
+use std::net::SocketAddr;
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+fn referrers() -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.index.v1+json",
+        "manifests": [
+            {
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "digest": "sha256:2222222222222222222222222222222222222222222222222222222222222222",
+                "size": 512,
+                "artifactType": "application/vnd.dev.cosign.artifact.sig.v1+json",
+            },
+            {
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "digest": "sha256:3333333333333333333333333333333333333333333333333333333333333333",
+                "size": 1024,
+                "artifactType": "application/spdx+json",
+            },
+        ],
+    }))
+    .unwrap()
+}
+
+async fn get(registry: SocketAddr, query: &str) -> hyper::Response<hyper::Body> {
+    let socket_addr =
+        start_server_with(&[("oci.base_address", &format!("http://{registry}"))]).await;
+
+    Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/app/referrers/{DIGEST}{query}")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+async fn parse_index(response: hyper::Response<hyper::Body>) -> ImageIndex {
+    let body = hyper::body::to_bytes(response).await.unwrap();
+
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn v2_referrers_are_filtered_by_artifact_type() {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .header(hyper::header::CONTENT_TYPE, OCI_IMAGE_INDEX)
+            .body(referrers().into())
+            .unwrap()
+    })
+    .await;
+
+    let response = get(registry, "?artifactType=application%2Fspdx%2Bjson").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!("artifactType", response.headers()["oci-filters-applied"]);
+
+    let index = parse_index(response).await;
+
+    assert_eq!(1, index.manifests.len());
+    assert_eq!(1024, index.manifests[0].size);
+}
+
+#[tokio::test]
+async fn v2_referrers_fall_back_to_tag_schema() {
+    let registry = start_mock(|parts, _| {
+        if parts.uri.path() == "/v2/app/manifests/sha256-1111111111111111111111111111111111111111111111111111111111111111" {
+            hyper::Response::builder()
+                .header(hyper::header::CONTENT_TYPE, OCI_IMAGE_INDEX)
+                .body(referrers().into())
+                .unwrap()
+        } else {
+            hyper::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(hyper::Body::empty())
+                .unwrap()
+        }
+    })
+    .await;
+
+    let response = get(
+        registry,
+        "?artifactType=application%2Fvnd.dev.cosign.artifact.sig.v1%2Bjson",
+    )
+    .await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert_eq!(OCI_IMAGE_INDEX, response.headers()["content-type"]);
+
+    let index = parse_index(response).await;
+
+    assert_eq!(1, index.manifests.len());
+    assert_eq!(512, index.manifests[0].size);
+}
+
+#[tokio::test]
+async fn v2_referrers_without_tag_schema_are_empty() {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let response = get(registry, "").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+    assert!(parse_index(response).await.manifests.is_empty());
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::{start_mock, start_server_with};
+use hmac::Mac as _;
+use hyper::{client::Client, StatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+const DIGEST: &str = "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+
+type Imported = Arc<Mutex<Vec<String>>>;
+
+/// Starts the gateway in front of a mock Snyk API, returning the names of the images it imported.
+async fn start() -> (SocketAddr, Imported) {
+    let imported = Imported::default();
+
+    let snyk = start_mock({
+        let imported = imported.clone();
+        move |_, body| {
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            imported
+                .lock()
+                .unwrap()
+                .push(body["target"]["name"].as_str().unwrap().to_string());
+
+            hyper::Response::builder()
+                .status(StatusCode::CREATED)
+                .body(hyper::Body::empty())
+                .unwrap()
+        }
+    })
+    .await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", "http://127.0.0.1:1"),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("registry_events.secret", "secret"),
+    ])
+    .await;
+
+    (socket_addr, imported)
+}
+
+async fn post(
+    socket_addr: SocketAddr,
+    header: Option<(&str, String)>,
+    payload: &serde_json::Value,
+) -> StatusCode {
+    let mut request = hyper::Request::post(format!("http://{socket_addr}/registry/events"));
+
+    if let Some((name, value)) = header {
+        request = request.header(name, value);
+    }
+
+    Client::new()
+        .request(
+            request
+                .body(serde_json::to_vec(payload).unwrap().into())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn registry_events_of_distribution_harbor_and_ecr_pushes_are_imported() {
+    let (socket_addr, imported) = start().await;
+
+    let payloads = [
+        serde_json::json!({
+            "events": [
+                {
+                    "action": "push",
+                    "target": {
+                        "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip",
+                        "repository": "team/app",
+                        "digest": DIGEST,
+                    },
+                },
+                {
+                    "action": "push",
+                    "target": {
+                        "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+                        "repository": "team/app",
+                        "digest": DIGEST,
+                        "tag": "v1",
+                    },
+                },
+                {
+                    "action": "pull",
+                    "target": { "repository": "team/app", "digest": DIGEST, "tag": "v0" },
+                },
+            ],
+        }),
+        serde_json::json!({
+            "type": "PUSH_ARTIFACT",
+            "event_data": {
+                "resources": [{ "digest": DIGEST, "tag": "v2" }],
+                "repository": { "name": "app", "namespace": "library", "repo_full_name": "library/app" },
+            },
+        }),
+        serde_json::json!({
+            "source": "aws.ecr",
+            "detail-type": "ECR Image Action",
+            "detail": {
+                "result": "SUCCESS",
+                "repository-name": "ecr/app",
+                "image-digest": DIGEST,
+                "action-type": "PUSH",
+                "image-tag": "v3",
+            },
+        }),
+    ];
+
+    for payload in &payloads {
+        let header = Some(("authorization", "Bearer secret".to_string()));
+
+        assert_eq!(
+            StatusCode::ACCEPTED,
+            post(socket_addr, header, payload).await
+        );
+    }
+
+    assert_eq!(
+        vec!["team/app:v1", "library/app:v2", "ecr/app:v3"],
+        *imported.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn registry_events_with_hmac_signature_are_imported() {
+    let (socket_addr, imported) = start().await;
+
+    let payload = serde_json::json!({
+        "events": [{ "action": "push", "target": { "repository": "app", "tag": "v1" } }],
+    });
+
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(b"secret").unwrap();
+    mac.update(&serde_json::to_vec(&payload).unwrap());
+    let signature = format!("sha256={:x}", mac.finalize().into_bytes());
+
+    let status = post(
+        socket_addr,
+        Some(("x-hub-signature-256", signature)),
+        &payload,
+    )
+    .await;
+
+    assert_eq!(StatusCode::ACCEPTED, status);
+    assert_eq!(vec!["app:v1"], *imported.lock().unwrap());
+}
+
+#[tokio::test]
+async fn registry_events_without_secret_are_rejected() {
+    let (socket_addr, imported) = start().await;
+
+    let payload = serde_json::json!({
+        "events": [{ "action": "push", "target": { "repository": "app", "tag": "v1" } }],
+    });
+
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        post(socket_addr, None, &payload).await
+    );
+    assert_eq!(
+        StatusCode::UNAUTHORIZED,
+        post(
+            socket_addr,
+            Some(("authorization", "Bearer guess".to_string())),
+            &payload
+        )
+        .await
+    );
+    assert!(imported.lock().unwrap().is_empty());
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::{
+    get_as_admin, load_configuration, start_mock, start_server_with_reload, start_snyk, ADMIN,
+};
+use hyper::{client::Client, StatusCode};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+async fn get(socket_addr: SocketAddr, path: &str) -> hyper::Response<hyper::Body> {
+    Client::new()
+        .get(format!("http://{socket_addr}{path}").parse().unwrap())
+        .await
+        .unwrap()
+}
+
+/// Waits for the status of pulls of the `path` to become `expected`.
+async fn wait_for_status(socket_addr: SocketAddr, path: &str, expected: StatusCode) {
+    for _ in 0..50 {
+        if get(socket_addr, path).await.status() == expected {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("Pulls of {path} never returned {expected}");
+}
+
+#[tokio::test]
+async fn reloaded_configuration_is_applied_only_when_valid() {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            )
+            .body(hyper::Body::from("{}"))
+            .unwrap()
+    })
+    .await;
+    let snyk = start_snyk(|_| None).await;
+
+    let registry = format!("http://{registry}");
+    let snyk = format!("http://{snyk}");
+    let overrides = |mode| {
+        vec![
+            ("oci.base_address", registry.as_str()),
+            ("snyk.base_address", snyk.as_str()),
+            ("enforcement.rules[0].repositories[0]", "canary/**"),
+            ("enforcement.rules[0].mode", mode),
+            ADMIN[0],
+            ADMIN[1],
+        ]
+    };
+
+    let (socket_addr, reloads) = start_server_with_reload(&overrides("warn")).await;
+
+    let response = get(socket_addr, "/v2/canary/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    reloads
+        .send(load_configuration(&overrides("enforce")))
+        .await
+        .unwrap();
+
+    wait_for_status(
+        socket_addr,
+        "/v2/canary/app/manifests/v1",
+        StatusCode::FORBIDDEN,
+    )
+    .await;
+
+    let mut invalid = overrides("warn");
+    // the cache directory cannot be created, which is only found when the state is created.
+    invalid.push(("cache.directory", "/dev/null/cache"));
+    invalid.push(("cache.max_size", "1024"));
+
+    // reloads are handled in order, once the third is queued the first has been handled.
+    for _ in 0..3 {
+        reloads.send(load_configuration(&invalid)).await.unwrap();
+    }
+
+    let response = get(socket_addr, "/v2/canary/app/manifests/v1").await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let metrics = hyper::body::to_bytes(get_as_admin(socket_addr, "/metrics").await)
+        .await
+        .unwrap();
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+
+    assert!(
+        metrics.contains("container_registry_gateway_admission_denials_total{mode=\"warn\"} 1"),
+        "{metrics}"
+    );
+}
+
+#[tokio::test]
+async fn reloaded_secret_is_applied() {
+    let authorization = Arc::new(Mutex::new(None));
+    let registry = start_mock({
+        let authorization = authorization.clone();
+        move |parts, _| {
+            *authorization.lock().unwrap() =
+                parts.headers.get(hyper::header::AUTHORIZATION).cloned();
+
+            hyper::Response::builder()
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    "application/vnd.oci.image.manifest.v1+json",
+                )
+                .body(hyper::Body::from("{}"))
+                .unwrap()
+        }
+    })
+    .await;
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let registry = format!("http://{registry}");
+    let snyk = format!("http://{snyk}");
+    let overrides = |password| {
+        vec![
+            ("oci.base_address", registry.as_str()),
+            ("oci.username", "gateway"),
+            ("oci.password", password),
+            ("snyk.base_address", snyk.as_str()),
+        ]
+    };
+
+    let (socket_addr, reloads) = start_server_with_reload(&overrides("first")).await;
+
+    get(socket_addr, "/v2/app/manifests/v1").await;
+
+    // gateway:first
+    assert_eq!(
+        Some("Basic Z2F0ZXdheTpmaXJzdA=="),
+        authorization
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|value| value.to_str().unwrap())
+    );
+
+    reloads
+        .send(load_configuration(&overrides("second")))
+        .await
+        .unwrap();
+
+    // gateway:second
+    let second = Some(hyper::header::HeaderValue::from_static(
+        "Basic Z2F0ZXdheTpzZWNvbmQ=",
+    ));
+
+    for _ in 0..50 {
+        get(socket_addr, "/v2/app/manifests/v1").await;
+
+        if *authorization.lock().unwrap() == second {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("The reloaded password was never sent");
+}
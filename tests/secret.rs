@@ -0,0 +1,227 @@
+mod common;
+
+use common::{start_mock, start_server_with_configuration, start_snyk};
+use container_registry_gateway::configuration;
+use hyper::client::Client;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+fn load_configuration(overrides: &[(&str, &str)]) -> configuration::Configuration {
+    let overrides = [
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+    ]
+    .iter()
+    .chain(overrides)
+    .copied()
+    .collect::<Vec<_>>();
+
+    configuration::load(&overrides).unwrap()
+}
+
+async fn pull(socket_addr: SocketAddr) {
+    Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/app/manifests/v1")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn snyk_api_key_file_is_read_again_on_rotation() {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .header(
+                hyper::header::CONTENT_TYPE,
+                "application/vnd.oci.image.manifest.v1+json",
+            )
+            .body(hyper::Body::from("{}"))
+            .unwrap()
+    })
+    .await;
+
+    let authorizations = Arc::new(Mutex::new(Vec::new()));
+    let snyk = start_mock({
+        let authorizations = authorizations.clone();
+        move |parts, _| {
+            authorizations.lock().unwrap().push(
+                parts.headers[hyper::header::AUTHORIZATION]
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            );
+            hyper::Response::new(hyper::Body::from(r#"{"projects":[]}"#))
+        }
+    })
+    .await;
+
+    let directory = std::env::temp_dir().join(format!("secret-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let api_key_file = directory.join("api-key");
+    std::fs::write(&api_key_file, "first\n").unwrap();
+
+    let socket_addr = start_server_with_configuration(load_configuration(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.api_key_file", api_key_file.to_str().unwrap()),
+        ("snyk.base_address", &format!("http://{snyk}")),
+    ]))
+    .await;
+
+    pull(socket_addr).await;
+
+    std::fs::write(&api_key_file, "second-key\n").unwrap();
+
+    pull(socket_addr).await;
+
+    assert_eq!(
+        vec!["token first", "token second-key"],
+        *authorizations.lock().unwrap()
+    );
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn upstream_password_file_is_sent_for_pulls_without_credentials() {
+    let authorizations = Arc::new(Mutex::new(Vec::new()));
+    let registry = start_mock({
+        let authorizations = authorizations.clone();
+        move |parts, _| {
+            authorizations.lock().unwrap().push((
+                parts.method.to_string(),
+                parts
+                    .headers
+                    .get(hyper::header::AUTHORIZATION)
+                    .map(|value| value.to_str().unwrap().to_string()),
+            ));
+
+            hyper::Response::builder()
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    "application/vnd.oci.image.manifest.v1+json",
+                )
+                .body(hyper::Body::from("{}"))
+                .unwrap()
+        }
+    })
+    .await;
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let directory = std::env::temp_dir().join(format!("upstream-secret-{}", registry.port()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let password_file = directory.join("password");
+    std::fs::write(&password_file, "first\n").unwrap();
+
+    let socket_addr = start_server_with_configuration(load_configuration(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("oci.username", "gateway"),
+        ("oci.password_file", password_file.to_str().unwrap()),
+        ("snyk.api_key", "key"),
+        ("snyk.base_address", &format!("http://{snyk}")),
+    ]))
+    .await;
+
+    let sent = || std::mem::take(&mut *authorizations.lock().unwrap());
+
+    pull(socket_addr).await;
+
+    // gateway:first
+    let first = Some("Basic Z2F0ZXdheTpmaXJzdA==".to_string());
+    let authorizations_sent = sent();
+
+    assert!(!authorizations_sent.is_empty());
+    assert!(authorizations_sent
+        .iter()
+        .all(|(_, authorization)| *authorization == first));
+
+    std::fs::write(&password_file, "second\n").unwrap();
+
+    pull(socket_addr).await;
+
+    // gateway:second
+    let second = Some("Basic Z2F0ZXdheTpzZWNvbmQ=".to_string());
+    let authorizations_sent = sent();
+
+    assert!(!authorizations_sent.is_empty());
+    assert!(authorizations_sent
+        .iter()
+        .all(|(_, authorization)| *authorization == second));
+
+    Client::new()
+        .request(
+            hyper::Request::delete(format!("http://{socket_addr}/v2/app/manifests/v1"))
+                .body(hyper::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(vec![("DELETE".to_string(), None)], sent());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn secrets_are_redacted_when_serialized() {
+    let configuration = load_configuration(&[
+        ("oci.base_address", "https://registry-1.docker.io"),
+        ("oci.username", "gateway"),
+        ("oci.password", "0123456789"),
+        ("snyk.api_key", "9876543210"),
+        ("snyk.base_address", "https://api.snyk.io"),
+        (
+            "notification.targets[0].url",
+            "https://hooks.slack.com/services/T000/B000/XXXXXXXX",
+        ),
+    ]);
+
+    let serialized = serde_json::to_value(&configuration).unwrap();
+
+    assert_eq!("[redacted]", serialized["oci"]["password"]);
+    assert_eq!("[redacted]", serialized["snyk"]["api_key"]);
+    assert_eq!(
+        "[redacted]",
+        serialized["notification"]["targets"][0]["url"]
+    );
+    assert_eq!("gateway", serialized["oci"]["username"]);
+}
+
+#[test]
+fn secrets_are_redacted_in_debug_output() {
+    let configuration = load_configuration(&[
+        ("oci.base_address", "https://registry-1.docker.io"),
+        ("snyk.api_key", "0123456789"),
+        ("snyk.base_address", "https://api.snyk.io"),
+    ]);
+
+    let debug = format!("{configuration:?}");
+
+    assert!(!debug.contains("0123456789"), "{debug}");
+    assert!(debug.contains("api_key: Some([redacted])"), "{debug}");
+}
+
+#[test]
+fn secret_and_secret_file_are_mutually_exclusive() {
+    let error = configuration::load(&[
+        ("oci.base_address", "https://registry-1.docker.io"),
+        ("snyk.api_key", "0123456789"),
+        ("snyk.api_key_file", "/var/run/secrets/snyk/api-key"),
+        ("snyk.base_address", "https://api.snyk.io"),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+    ])
+    .err()
+    .unwrap();
+
+    assert!(
+        error
+            .to_string()
+            .contains("snyk.api_key and snyk.api_key_file are mutually exclusive"),
+        "{error}"
+    );
+}
@@ -1,10 +1,8 @@
-use container_registry_gateway::{
-    configuration,
-    oci::{Response, ResponseError},
-    server, shutdown,
-};
-use hyper::{body::Buf as _, client::Client, StatusCode};
-use std::net::SocketAddr;
+mod common;
+
+use common::{parse_body, start_server};
+use container_registry_gateway::oci::{Response, ResponseError};
+use hyper::{client::Client, StatusCode};
 
 #[tokio::test]
 async fn root_returns_not_found() {
@@ -82,35 +80,3 @@ async fn v2_root_returns_unauthorized() {
         body
     );
 }
-
-async fn start_server() -> SocketAddr {
-    let tcp_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
-
-    let configuration = configuration::load(&[
-        ("oci.base_address", "https://registry-1.docker.io"),
-        ("snyk.api_key", ""),
-        ("snyk.base_address", ""),
-        ("snyk.integration_id", ""),
-        ("snyk.organization_id", ""),
-    ])
-    .unwrap();
-
-    let socket_addr = tcp_listener.local_addr().unwrap();
-
-    tokio::spawn(async move {
-        server::run(
-            tcp_listener.into_std().unwrap(),
-            shutdown::recv(),
-            configuration,
-        )
-        .await
-    });
-
-    socket_addr
-}
-
-async fn parse_body(response: hyper::Response<hyper::Body>) -> Response {
-    let buffer = hyper::body::aggregate(response).await.unwrap();
-
-    serde_json::from_reader(buffer.reader()).unwrap()
-}
@@ -0,0 +1,168 @@
+mod common;
+
+use base64::Engine as _;
+use common::{parse_body, start_mock, start_server_with, start_snyk};
+use hyper::{client::Client, StatusCode};
+use p256::{
+    ecdsa::{signature::Signer as _, Signature, SigningKey},
+    pkcs8::{EncodePublicKey as _, LineEnding},
+};
+use sha2::Digest as _;
+use std::{collections::HashMap, net::SocketAddr};
+
+fn digest(content: &[u8]) -> String {
+    format!("sha256:{:x}", sha2::Sha256::digest(content))
+}
+
+fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+}
+
+/// Starts a mock registry serving `app:latest`, signed by `signing_key` if any.
+async fn start_registry(signing_key: Option<SigningKey>) -> SocketAddr {
+    let manifest = serde_json::to_vec(&serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": digest(b"{}"),
+            "size": 2,
+        },
+        "layers": [],
+    }))
+    .unwrap();
+    let manifest_digest = digest(&manifest);
+
+    let mut manifests = HashMap::new();
+    let mut blobs = HashMap::new();
+
+    manifests.insert("latest".to_string(), manifest.clone());
+    manifests.insert(manifest_digest.clone(), manifest);
+
+    if let Some(signing_key) = signing_key {
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "critical": {
+                "identity": { "docker-reference": "registry.local/app" },
+                "image": { "docker-manifest-digest": manifest_digest },
+                "type": "cosign container image signature",
+            },
+            "optional": null,
+        }))
+        .unwrap();
+
+        let signature: Signature = signing_key.sign(&payload);
+
+        let signature_manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": {
+                "mediaType": "application/vnd.oci.image.config.v1+json",
+                "digest": digest(b"{}"),
+                "size": 2,
+            },
+            "layers": [{
+                "mediaType": "application/vnd.dev.cosign.simplesigning.v1+json",
+                "digest": digest(&payload),
+                "size": payload.len(),
+                "annotations": {
+                    "dev.cosignproject.cosign/signature":
+                        base64::engine::general_purpose::STANDARD.encode(signature.to_der()),
+                },
+            }],
+        }))
+        .unwrap();
+
+        manifests.insert(
+            format!("{}.sig", manifest_digest.replace(':', "-")),
+            signature_manifest,
+        );
+        blobs.insert(digest(&payload), payload);
+    }
+
+    start_mock(move |parts, _| {
+        let path = parts.uri.path();
+
+        let found = path
+            .strip_prefix("/v2/app/manifests/")
+            .and_then(|reference| manifests.get(reference))
+            .map(|manifest| (manifest, "application/vnd.oci.image.manifest.v1+json"))
+            .or_else(|| {
+                path.strip_prefix("/v2/app/blobs/")
+                    .and_then(|digest| blobs.get(digest))
+                    .map(|blob| (blob, "application/octet-stream"))
+            });
+
+        match found {
+            Some((body, media_type)) => hyper::Response::builder()
+                .header(hyper::header::CONTENT_TYPE, media_type)
+                .header("docker-content-digest", digest(body))
+                .body(body.clone().into())
+                .unwrap(),
+            None => hyper::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(hyper::Body::empty())
+                .unwrap(),
+        }
+    })
+    .await
+}
+
+async fn pull(registry: SocketAddr, public_key: &SigningKey) -> hyper::Response<hyper::Body> {
+    let snyk = start_snyk(|_| Some([0, 0, 0, 0])).await;
+
+    let public_key = public_key
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .unwrap();
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{registry}")),
+        ("snyk.base_address", &format!("http://{snyk}")),
+        ("signature.public_keys[0]", &public_key),
+    ])
+    .await;
+
+    Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/app/manifests/latest")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn v2_manifest_signed_by_trusted_key_is_admitted() {
+    let registry = start_registry(Some(signing_key(1))).await;
+
+    let response = pull(registry, &signing_key(1)).await;
+
+    assert_eq!(StatusCode::OK, response.status());
+}
+
+#[tokio::test]
+async fn v2_manifest_signed_by_untrusted_key_is_denied() {
+    let registry = start_registry(Some(signing_key(2))).await;
+
+    let response = pull(registry, &signing_key(1)).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image signature could not be verified",
+        parse_body(response).await.errors[0].message
+    );
+}
+
+#[tokio::test]
+async fn v2_manifest_without_signature_is_denied() {
+    let registry = start_registry(None).await;
+
+    let response = pull(registry, &signing_key(1)).await;
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+    assert_eq!(
+        "Image signature not found",
+        parse_body(response).await.errors[0].message
+    );
+}
@@ -0,0 +1,283 @@
+mod common;
+
+use common::{load_configuration, start_mock, start_server_with_configuration};
+use hyper::StatusCode;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+};
+use std::{net::SocketAddr, path::Path};
+
+fn certificate_authority(name: &str) -> Certificate {
+    let mut params = CertificateParams::new(Vec::new());
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.distinguished_name.push(DnType::CommonName, name);
+
+    Certificate::from_params(params).unwrap()
+}
+
+fn certificate(name: &str, purpose: ExtendedKeyUsagePurpose) -> Certificate {
+    let mut params = CertificateParams::new(vec![name.to_string()]);
+    params.distinguished_name.push(DnType::CommonName, name);
+    params.extended_key_usages = vec![purpose];
+
+    Certificate::from_params(params).unwrap()
+}
+
+/// Writes a server certificate for `localhost` signed by the `issuer`.
+fn write_server_certificate(directory: &Path, issuer: &Certificate) {
+    let server = certificate("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+
+    std::fs::write(
+        directory.join("tls.crt"),
+        server.serialize_pem_with_signer(issuer).unwrap(),
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("tls.key"),
+        server.serialize_private_key_pem(),
+    )
+    .unwrap();
+}
+
+/// Sends a request over TLS, trusting the `issuer` and presenting the client certificate if any.
+async fn request(
+    socket_addr: SocketAddr,
+    method: hyper::Method,
+    path: &str,
+    issuer: &Certificate,
+    client: Option<(&Certificate, &Certificate)>,
+    headers: &[(&str, &str)],
+) -> hyper::Result<hyper::Response<hyper::Body>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(&rustls::Certificate(issuer.serialize_der().unwrap()))
+        .unwrap();
+
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    let tls_config = match client {
+        Some((client, client_issuer)) => builder
+            .with_single_cert(
+                vec![rustls::Certificate(
+                    client.serialize_der_with_signer(client_issuer).unwrap(),
+                )],
+                rustls::PrivateKey(client.serialize_private_key_der()),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_only()
+        .enable_http1()
+        .build();
+
+    let mut request = hyper::Request::builder()
+        .method(method)
+        .uri(format!("https://localhost:{}{path}", socket_addr.port()));
+
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    hyper::Client::builder()
+        .build::<_, hyper::Body>(connector)
+        .request(request.body(hyper::Body::empty()).unwrap())
+        .await
+}
+
+#[tokio::test]
+async fn tls_maps_client_certificates_to_principals_and_reloads_certificate() {
+    let registry = start_mock(|_, _| {
+        hyper::Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let directory = std::env::temp_dir().join(format!("tls-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let server_issuer = certificate_authority("Server CA");
+    let client_issuer = certificate_authority("Client CA");
+    let robot = certificate("ci-robot", ExtendedKeyUsagePurpose::ClientAuth);
+
+    write_server_certificate(&directory, &server_issuer);
+    std::fs::write(
+        directory.join("client-ca.crt"),
+        client_issuer.serialize_pem().unwrap(),
+    )
+    .unwrap();
+
+    let path = |name: &str| directory.join(name).to_str().unwrap().to_string();
+
+    let socket_addr = start_server_with_configuration(load_configuration(&[
+        ("http_server.tls.certificate", &path("tls.crt")),
+        ("http_server.tls.key", &path("tls.key")),
+        ("http_server.tls.client_ca", &path("client-ca.crt")),
+        ("oci.base_address", &format!("http://{registry}")),
+        ("delete.rules[0].principals[0]", "ci-robot"),
+    ]))
+    .await;
+
+    let manifest = "/v2/app/manifests/latest";
+
+    let response = request(
+        socket_addr,
+        hyper::Method::GET,
+        "/health/liveness",
+        &server_issuer,
+        None,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    let response = request(
+        socket_addr,
+        hyper::Method::DELETE,
+        manifest,
+        &server_issuer,
+        None,
+        &[("x-gateway-client-certificate", "ci-robot")],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, response.status());
+
+    let response = request(
+        socket_addr,
+        hyper::Method::DELETE,
+        manifest,
+        &server_issuer,
+        Some((&robot, &client_issuer)),
+        &[],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+
+    let renewed_issuer = certificate_authority("Renewed Server CA");
+
+    assert!(request(
+        socket_addr,
+        hyper::Method::GET,
+        "/health/liveness",
+        &renewed_issuer,
+        None,
+        &[],
+    )
+    .await
+    .is_err());
+
+    write_server_certificate(&directory, &renewed_issuer);
+
+    let response = request(
+        socket_addr,
+        hyper::Method::GET,
+        "/health/liveness",
+        &renewed_issuer,
+        None,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn tls_serves_previous_certificate_until_key_matches() {
+    let directory = std::env::temp_dir().join(format!("tls-key-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let server_issuer = certificate_authority("Server CA");
+
+    write_server_certificate(&directory, &server_issuer);
+
+    let path = |name: &str| directory.join(name).to_str().unwrap().to_string();
+
+    let socket_addr = start_server_with_configuration(load_configuration(&[
+        ("http_server.tls.certificate", &path("tls.crt")),
+        ("http_server.tls.key", &path("tls.key")),
+        ("oci.base_address", "http://127.0.0.1:9"),
+    ]))
+    .await;
+
+    let liveness = |issuer| {
+        request(
+            socket_addr,
+            hyper::Method::GET,
+            "/health/liveness",
+            issuer,
+            None,
+            &[],
+        )
+    };
+
+    assert_eq!(
+        StatusCode::OK,
+        liveness(&server_issuer).await.unwrap().status()
+    );
+
+    let renewed_issuer = certificate_authority("Renewed Server CA");
+    let renewed = certificate("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+
+    // the certificate is replaced before its key.
+    std::fs::write(
+        directory.join("tls.crt"),
+        renewed.serialize_pem_with_signer(&renewed_issuer).unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        StatusCode::OK,
+        liveness(&server_issuer).await.unwrap().status()
+    );
+    assert!(liveness(&renewed_issuer).await.is_err());
+
+    std::fs::write(
+        directory.join("tls.key"),
+        renewed.serialize_private_key_pem(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        StatusCode::OK,
+        liveness(&renewed_issuer).await.unwrap().status()
+    );
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn required_client_certificate_requires_client_ca() {
+    let error = container_registry_gateway::configuration::load(&[
+        ("http_server.tls.certificate", "/etc/tls/tls.crt"),
+        ("http_server.tls.key", "/etc/tls/tls.key"),
+        ("http_server.tls.client_certificate_required", "true"),
+        ("oci.base_address", "https://registry-1.docker.io"),
+        ("snyk.api_key", "key"),
+        ("snyk.base_address", "https://api.snyk.io"),
+        ("snyk.integration_id", "integration"),
+        ("snyk.organization_id", "organization"),
+    ])
+    .err()
+    .unwrap();
+
+    assert!(
+        error
+            .to_string()
+            .contains("http_server.tls.client_certificate_required requires"),
+        "{error}"
+    );
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use common::{parse_body, start_mock, start_server_with};
+use hyper::{client::Client, header, StatusCode};
+
+#[tokio::test]
+async fn v2_prefixed_repository_is_forwarded_to_upstream_without_prefix() {
+    let default = start_mock(|_, _| hyper::Response::new("default".into())).await;
+    let upstream = start_mock(|parts, _| hyper::Response::new(parts.uri.to_string().into())).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{default}")),
+        ("oci.upstreams[0].prefix", "mirror"),
+        (
+            "oci.upstreams[0].base_address",
+            &format!("http://{upstream}"),
+        ),
+    ])
+    .await;
+
+    let response = Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/mirror/library/nginx/blobs/sha256:abc?n=1")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::OK, response.status());
+
+    let body = hyper::body::to_bytes(response).await.unwrap();
+
+    assert_eq!("/v2/library/nginx/blobs/sha256:abc?n=1", body);
+}
+
+#[tokio::test]
+async fn v2_unprefixed_repository_is_forwarded_to_default() {
+    let default = start_mock(|parts, _| hyper::Response::new(parts.uri.to_string().into())).await;
+    let upstream = start_mock(|_, _| hyper::Response::new("upstream".into())).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{default}")),
+        ("oci.upstreams[0].prefix", "mirror"),
+        (
+            "oci.upstreams[0].base_address",
+            &format!("http://{upstream}"),
+        ),
+    ])
+    .await;
+
+    let response = Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/mirrored/nginx/blobs/sha256:abc")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response).await.unwrap();
+
+    assert_eq!("/v2/mirrored/nginx/blobs/sha256:abc", body);
+}
+
+#[tokio::test]
+async fn v2_prefixed_repository_location_is_rewritten() {
+    let upstream = start_mock(|_, _| {
+        hyper::Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .header(header::LOCATION, "/v2/library/nginx/blobs/uploads/1234")
+            .body(hyper::Body::empty())
+            .unwrap()
+    })
+    .await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", "http://127.0.0.1:1"),
+        ("oci.upstreams[0].prefix", "mirror"),
+        (
+            "oci.upstreams[0].base_address",
+            &format!("http://{upstream}"),
+        ),
+    ])
+    .await;
+
+    let response = Client::new()
+        .request(
+            hyper::Request::post(format!(
+                "http://{socket_addr}/v2/mirror/library/nginx/blobs/uploads/"
+            ))
+            .body(hyper::Body::empty())
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::ACCEPTED, response.status());
+    assert_eq!(
+        "/v2/mirror/library/nginx/blobs/uploads/1234",
+        response.headers()[header::LOCATION]
+    );
+}
+
+#[tokio::test]
+async fn v2_mirror_namespace_is_forwarded_to_registry_upstream() {
+    let default = start_mock(|_, _| hyper::Response::new("default".into())).await;
+    let upstream = start_mock(|parts, _| hyper::Response::new(parts.uri.to_string().into())).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{default}")),
+        ("oci.mirror", "true"),
+        ("oci.upstreams[0].registry", "docker.io"),
+        (
+            "oci.upstreams[0].base_address",
+            &format!("http://{upstream}"),
+        ),
+    ])
+    .await;
+
+    let response = Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/nginx/blobs/sha256:abc?ns=docker.io")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = hyper::body::to_bytes(response).await.unwrap();
+
+    assert_eq!("/v2/library/nginx/blobs/sha256:abc", body);
+}
+
+#[tokio::test]
+async fn v2_mirror_unknown_namespace_is_not_served() {
+    let default = start_mock(|_, _| hyper::Response::new("default".into())).await;
+
+    let socket_addr = start_server_with(&[
+        ("oci.base_address", &format!("http://{default}")),
+        ("oci.mirror", "true"),
+        ("oci.registry", "docker.io"),
+    ])
+    .await;
+
+    let response = Client::new()
+        .get(
+            format!("http://{socket_addr}/v2/x/y/manifests/latest?ns=ghcr.io")
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(StatusCode::NOT_FOUND, response.status());
+    assert_eq!("NAME_UNKNOWN", parse_body(response).await.errors[0].code);
+}